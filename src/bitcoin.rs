@@ -0,0 +1,302 @@
+use bech32::primitives::decode::SegwitHrpstring;
+use bech32::{Fe32, Hrp};
+use pgrx::prelude::*;
+
+use crate::Error;
+
+extension_sql!(
+    "\
+CREATE TYPE BitcoinAddressInfo AS (
+    network text,
+    witness_version smallint,
+    kind text,
+    program bytea,
+    standard boolean
+);",
+    name = "create_bitcoin_address_info_type",
+);
+
+const ADDRESS_INFO_COMPOSITE_TYPE: &str = "BitcoinAddressInfo";
+
+/// The Bitcoin networks with a segwit `Hrp` (Human Readable Part).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    /// Testnet and signet share the `tb` `Hrp`.
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    /// Parses a network name, or the `Hrp` used on that network.
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            "mainnet" | "bc" => Ok(Network::Mainnet),
+            "testnet" | "signet" | "tb" => Ok(Network::Testnet),
+            "regtest" | "bcrt" => Ok(Network::Regtest),
+            _ => Err(Error::UnknownNetwork(name.to_string())),
+        }
+    }
+
+    pub fn from_hrp(hrp: &Hrp) -> Option<Self> {
+        match hrp.to_lowercase().as_str() {
+            "bc" => Some(Network::Mainnet),
+            "tb" => Some(Network::Testnet),
+            "bcrt" => Some(Network::Regtest),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Testnet => "testnet",
+            Network::Regtest => "regtest",
+        }
+    }
+
+    pub fn hrp(self) -> Hrp {
+        match self {
+            Network::Mainnet => bech32::hrp::BC,
+            Network::Testnet => bech32::hrp::TB,
+            Network::Regtest => bech32::hrp::BCRT,
+        }
+    }
+}
+
+/// The output type of a witness program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    P2wpkh,
+    P2wsh,
+    P2tr,
+    /// A valid witness program without a defined spending rule, which is not relayed as standard.
+    UnknownWitness,
+}
+
+impl Kind {
+    pub fn classify(version: Fe32, program: &[u8]) -> Self {
+        match (version.to_u8(), program.len()) {
+            (0, 20) => Kind::P2wpkh,
+            (0, 32) => Kind::P2wsh,
+            (1, 32) => Kind::P2tr,
+            _ => Kind::UnknownWitness,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::P2wpkh => "p2wpkh",
+            Kind::P2wsh => "p2wsh",
+            Kind::P2tr => "p2tr",
+            Kind::UnknownWitness => "unknown_witness",
+        }
+    }
+
+    pub fn is_standard(self) -> bool {
+        self != Kind::UnknownWitness
+    }
+}
+
+/// Builds the `scriptPubKey` of a witness program: the version opcode followed by a single push of
+/// the program.
+pub fn witness_script(version: Fe32, program: &[u8]) -> Vec<u8> {
+    let opcode = match version.to_u8() {
+        0 => 0x00,
+        // OP_1 through OP_16.
+        v => 0x50 + v,
+    };
+
+    let mut script = Vec::with_capacity(2 + program.len());
+    script.push(opcode);
+    script.push(program.len() as u8);
+    script.extend_from_slice(program);
+    script
+}
+
+/// Splits a `scriptPubKey` into its witness version and program, following the BIP-141 definition
+/// of a witness program. Returns `None` for any other script.
+pub fn parse_witness_script(script: &[u8]) -> Option<(Fe32, &[u8])> {
+    let (&opcode, rest) = script.split_first()?;
+    let (&push, program) = rest.split_first()?;
+
+    let version = match opcode {
+        0x00 => 0,
+        0x51..=0x60 => opcode - 0x50,
+        _ => return None,
+    };
+    if !(2..=40).contains(&program.len()) || push as usize != program.len() {
+        return None;
+    }
+
+    Fe32::try_from(version)
+        .ok()
+        .map(|version| (version, program))
+}
+
+/// Convert a witness program `scriptPubKey` (P2WPKH, P2WSH, P2TR or a future witness version) into
+/// its segwit address on `network`.
+///
+/// `network` is one of `mainnet` (bc), `testnet` or `signet` (tb) and `regtest` (bcrt). Returns
+/// `NULL` for scripts that have no segwit address, such as P2PKH or P2SH outputs.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bitcoin_script_to_address(script: &[u8], network: &str) -> Option<String> {
+    let network = Network::from_name(network).unwrap_or_else(|e| e.report());
+    let (version, program) = parse_witness_script(script)?;
+
+    bech32::segwit::encode(network.hrp(), version, program).ok()
+}
+
+/// Convert a segwit address into its witness program `scriptPubKey`.
+///
+/// Only addresses with a Bitcoin `Hrp`, `bc`, `tb` or `bcrt`, are accepted, so the segwit addresses
+/// of other chains are not mistaken for Bitcoin outputs.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bitcoin_address_to_script(input: &str) -> Vec<u8> {
+    let segwit = SegwitHrpstring::new(input).unwrap_or_else(|e| Error::segwit(input, e).report());
+    if Network::from_hrp(&segwit.hrp()).is_none() {
+        Error::UnknownNetwork(segwit.hrp().to_lowercase()).report();
+    }
+    let program: Vec<u8> = segwit.byte_iter().collect();
+
+    witness_script(segwit.witness_version(), &program)
+}
+
+/// Classify a segwit address into its `network`, `witness_version`, `kind` (`p2wpkh`, `p2wsh`,
+/// `p2tr` or `unknown_witness`) and witness `program`.
+///
+/// `network` is inferred from the `Hrp` as `mainnet` (bc), `testnet` (tb, shared with signet) or
+/// `regtest` (bcrt), and is `NULL` for any other `Hrp`. `standard` is false for valid programs that
+/// are not relayed as standard outputs.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bitcoin_address_info(
+    input: &str,
+) -> pgrx::composite_type!('static, ADDRESS_INFO_COMPOSITE_TYPE) {
+    let segwit = SegwitHrpstring::new(input).unwrap_or_else(|e| Error::segwit(input, e).report());
+    let program: Vec<u8> = segwit.byte_iter().collect();
+    let kind = Kind::classify(segwit.witness_version(), &program);
+
+    let mut info =
+        PgHeapTuple::new_composite_type(ADDRESS_INFO_COMPOSITE_TYPE).unwrap_or_else(|_| {
+            panic!(
+                "error creating {} composite type",
+                ADDRESS_INFO_COMPOSITE_TYPE
+            )
+        });
+    info.set_by_name(
+        "network",
+        Network::from_hrp(&segwit.hrp()).map(Network::as_str),
+    )
+    .expect("error setting network");
+    info.set_by_name(
+        "witness_version",
+        i16::from(segwit.witness_version().to_u8()),
+    )
+    .expect("error setting witness_version");
+    info.set_by_name("kind", kind.as_str())
+        .expect("error setting kind");
+    info.set_by_name("program", program)
+        .expect("error setting program");
+    info.set_by_name("standard", kind.is_standard())
+        .expect("error setting standard");
+    info
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const P2WPKH: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const P2WPKH_SCRIPT: &str = "0014751e76e8199196d454941c45d1b3a323f1433bd6";
+    const P2WSH: &str = "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3";
+    const P2WSH_SCRIPT: &str =
+        "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262";
+    const P2TR: &str = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+    const P2TR_SCRIPT: &str =
+        "512079be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    #[pg_test]
+    fn test_bitcoin_address_to_script() {
+        for (address, script) in [
+            (P2WPKH, P2WPKH_SCRIPT),
+            (P2WSH, P2WSH_SCRIPT),
+            (P2TR, P2TR_SCRIPT),
+        ] {
+            assert_eq!(hex::encode(bitcoin_address_to_script(address)), script);
+        }
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "unknown bitcoin network: \"ltc\"")]
+    fn test_bitcoin_address_to_script_rejects_other_chains() {
+        bitcoin_address_to_script("ltc1qw508d6qejxtdg4y5r3zarvary0c5xw7kgmn4n9");
+    }
+
+    #[pg_test]
+    fn test_bitcoin_script_to_address() {
+        for (address, script) in [
+            (P2WPKH, P2WPKH_SCRIPT),
+            (P2WSH, P2WSH_SCRIPT),
+            (P2TR, P2TR_SCRIPT),
+        ] {
+            let script = hex::decode(script).unwrap();
+            assert_eq!(
+                bitcoin_script_to_address(&script, "mainnet").as_deref(),
+                Some(address)
+            );
+        }
+    }
+
+    #[pg_test]
+    fn test_bitcoin_script_to_address_regtest() {
+        let script = hex::decode(P2WPKH_SCRIPT).unwrap();
+        assert_eq!(
+            bitcoin_script_to_address(&script, "bcrt").as_deref(),
+            Some("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080")
+        );
+    }
+
+    #[pg_test]
+    fn test_bitcoin_script_to_address_p2pkh() {
+        let script = hex::decode("76a914751e76e8199196d454941c45d1b3a323f1433bd688ac").unwrap();
+        assert_eq!(bitcoin_script_to_address(&script, "mainnet"), None);
+    }
+
+    #[pg_test]
+    fn test_bitcoin_address_info() {
+        for (address, kind) in [(P2WPKH, "p2wpkh"), (P2WSH, "p2wsh"), (P2TR, "p2tr")] {
+            let info = bitcoin_address_info(address);
+            assert_eq!(info.get_by_name("network").unwrap(), Some("mainnet"));
+            assert_eq!(info.get_by_name("kind").unwrap(), Some(kind));
+            assert_eq!(info.get_by_name("standard").unwrap(), Some(true));
+        }
+    }
+
+    #[pg_test]
+    fn test_bitcoin_address_info_unknown_witness() {
+        // BIP-350: a valid version 16 address with a 2 byte program.
+        let info = bitcoin_address_info("BC1SW50QGDZ25J");
+        assert_eq!(
+            info.get_by_name::<i16>("witness_version").unwrap(),
+            Some(16)
+        );
+        assert_eq!(info.get_by_name("kind").unwrap(), Some("unknown_witness"));
+        assert_eq!(info.get_by_name("standard").unwrap(), Some(false));
+    }
+
+    #[pg_test]
+    fn test_bitcoin_address_info_testnet() {
+        let info =
+            bitcoin_address_info("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7");
+        assert_eq!(info.get_by_name("network").unwrap(), Some("testnet"));
+        assert_eq!(info.get_by_name("kind").unwrap(), Some("p2wsh"));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "unknown bitcoin network")]
+    fn test_bitcoin_script_to_address_unknown_network() {
+        let script = hex::decode(P2WPKH_SCRIPT).unwrap();
+        bitcoin_script_to_address(&script, "litecoin");
+    }
+}
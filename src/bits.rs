@@ -0,0 +1,138 @@
+use pgrx::prelude::*;
+
+use crate::error::non_null;
+use crate::Error;
+
+/// The largest group size, so that 8-bit groups fit in a `bytea` and every group in a `smallint`.
+const MAX_BITS: u32 = 8;
+
+/// Regroup `input` from groups of `from_bits` into groups of `to_bits`, the `convertbits`
+/// operation of BIP-173.
+///
+/// With `pad`, the last group is padded with zero bits. Without it, the input must end in fewer
+/// than `from_bits` bits of zero padding, as when decoding a bech32 data part.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_convert_bits(input: Array<i16>, from_bits: i32, to_bits: i32, pad: bool) -> Vec<i16> {
+    non_null("input", input)
+        .and_then(|input| convert_bits(input, from_bits, to_bits, pad))
+        .unwrap_or_else(|e| e.report())
+}
+
+/// Regroup the bytes of `input` into groups of `to_bits`, where `from_bits` is usually 8.
+#[pg_extern(immutable, parallel_safe, name = "bech32_convert_bits")]
+pub fn bech32_convert_bits_bytea(
+    input: &[u8],
+    from_bits: i32,
+    to_bits: i32,
+    pad: bool,
+) -> Vec<i16> {
+    let input = input.iter().map(|&b| i16::from(b));
+    convert_bits(input, from_bits, to_bits, pad).unwrap_or_else(|e| e.report())
+}
+
+pub fn convert_bits(
+    input: impl IntoIterator<Item = i16>,
+    from_bits: i32,
+    to_bits: i32,
+    pad: bool,
+) -> Result<Vec<i16>, Error> {
+    let group_size = |bits: i32| {
+        u32::try_from(bits)
+            .ok()
+            .filter(|bits| (1..=MAX_BITS).contains(bits))
+            .ok_or_else(|| Error::ConvertBits {
+                reason: format!(
+                    "group sizes must be between 1 and {} bits, not {}",
+                    MAX_BITS, bits
+                ),
+            })
+    };
+    let (from_bits, to_bits) = (group_size(from_bits)?, group_size(to_bits)?);
+
+    let max_value = (1 << to_bits) - 1;
+    let max_acc = (1 << (from_bits + to_bits - 1)) - 1;
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    let mut output = Vec::new();
+
+    for value in input {
+        let value = u32::try_from(value)
+            .ok()
+            .filter(|value| value >> from_bits == 0)
+            .ok_or_else(|| Error::ConvertBits {
+                reason: format!("value {} does not fit in {} bits", value, from_bits),
+            })?;
+        acc = ((acc << from_bits) | value) & max_acc;
+        bits += from_bits;
+        while bits >= to_bits {
+            bits -= to_bits;
+            output.push(((acc >> bits) & max_value) as i16);
+        }
+    }
+
+    if pad {
+        if bits > 0 {
+            output.push(((acc << (to_bits - bits)) & max_value) as i16);
+        }
+    } else if bits >= from_bits {
+        return Err(Error::ConvertBits {
+            reason: "the input has too many bits of padding".to_string(),
+        });
+    } else if (acc << (to_bits - bits)) & max_value != 0 {
+        return Err(Error::ConvertBits {
+            reason: "the input is padded with non-zero bits".to_string(),
+        });
+    }
+
+    Ok(output)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    #[pg_test]
+    fn test_bech32_convert_bits() {
+        assert_eq!(
+            bech32_convert_bits_bytea(&[0x00, 0xff], 8, 5, true),
+            vec![0, 3, 31, 16]
+        );
+        assert_eq!(
+            convert_bits(vec![0, 3, 31, 16], 5, 8, false),
+            Ok(vec![0, 255])
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_convert_bits_sql() {
+        let result =
+            Spi::get_one::<Vec<i16>>("SELECT bech32_convert_bits('\\x00ff'::bytea, 8, 5, true)");
+        assert_eq!(result, Ok(Some(vec![0, 3, 31, 16])));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot convert bits")]
+    fn test_bech32_convert_bits_rejects_non_zero_padding() {
+        Spi::run("SELECT bech32_convert_bits(ARRAY[0, 3, 31, 17]::smallint[], 5, 8, false)")
+            .unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot convert bits")]
+    fn test_bech32_convert_bits_rejects_too_much_padding() {
+        Spi::run("SELECT bech32_convert_bits(ARRAY[0]::smallint[], 5, 8, false)").unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot convert bits")]
+    fn test_bech32_convert_bits_rejects_large_value() {
+        Spi::run("SELECT bech32_convert_bits(ARRAY[32]::smallint[], 5, 8, true)").unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "array argument \"input\" must not contain nulls")]
+    fn test_bech32_convert_bits_rejects_null_element() {
+        Spi::run("SELECT bech32_convert_bits(ARRAY[0, NULL]::smallint[], 5, 8, true)").unwrap();
+    }
+}
@@ -0,0 +1,356 @@
+use bech32::primitives::decode::UncheckedHrpstring;
+use bech32::Fe32;
+use pgrx::prelude::*;
+use pgrx::JsonB;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, Secp256k1};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::bits::convert_bits;
+use crate::types::UnboundedBech32;
+use crate::{Error, Variant};
+
+/// The number of field elements of the timestamp and of the recoverable signature.
+const TIMESTAMP_LENGTH: usize = 7;
+const SIGNATURE_LENGTH: usize = 104;
+
+/// The number of field elements of a 32 byte hash and of a 33 byte public key.
+const HASH_LENGTH: usize = 52;
+const PUBKEY_LENGTH: usize = 53;
+
+/// The length in bytes of a hop in a route hint.
+const HOP_LENGTH: usize = 51;
+
+/// The expiry in seconds and the `min_final_cltv_expiry` of an invoice without those fields.
+const DEFAULT_EXPIRY: u64 = 3600;
+const DEFAULT_MIN_FINAL_CLTV_EXPIRY: u64 = 18;
+
+/// Decode a BOLT11 Lightning invoice into a `jsonb` object.
+///
+/// The object holds the `network`, `amount_msat` (`NULL` for an invoice without an amount),
+/// `timestamp`, `payment_hash`, `payment_secret`, `description` or `description_hash`, `expiry`,
+/// `min_final_cltv_expiry`, `route_hints`, `features` as a list of bit numbers, and the
+/// `signature` with its `recovery_id`. `payee` is the node id recovered from the signature, which
+/// must match the `n` field when the invoice has one.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bolt11_decode(input: &str) -> JsonB {
+    decode(input).map(JsonB).unwrap_or_else(|e| e.report())
+}
+
+fn decode(input: &str) -> Result<Value, Error> {
+    let unchecked = UncheckedHrpstring::new(input).map_err(|e| Error::parse(input, e))?;
+    let checked = unchecked
+        .validate_and_remove_checksum::<UnboundedBech32>()
+        .map_err(|e| Error::checksum(input, e, Some(Variant::Bech32)))?;
+
+    let hrp = checked.hrp().to_lowercase();
+    let (network, amount_msat) = parse_hrp(&hrp)?;
+
+    let fes: Vec<u8> = checked
+        .data_part_ascii_no_checksum()
+        .iter()
+        .map(|&c| {
+            let fe = Fe32::from_char(char::from(c)).expect("data part was validated");
+            fe.to_u8()
+        })
+        .collect();
+    if fes.len() < TIMESTAMP_LENGTH + SIGNATURE_LENGTH {
+        return Err(invalid("the invoice is too short"));
+    }
+    let (data, signature) = fes.split_at(fes.len() - SIGNATURE_LENGTH);
+    let (timestamp, mut fields) = data.split_at(TIMESTAMP_LENGTH);
+
+    let mut invoice = json!({
+        "network": network,
+        "amount_msat": amount_msat,
+        "timestamp": int(timestamp, "timestamp")?,
+        "payment_hash": null,
+        "payment_secret": null,
+        "description": null,
+        "description_hash": null,
+        "expiry": DEFAULT_EXPIRY,
+        "min_final_cltv_expiry": DEFAULT_MIN_FINAL_CLTV_EXPIRY,
+        "route_hints": [],
+        "features": [],
+    });
+    let mut node_id = None;
+
+    while !fields.is_empty() {
+        let [tag, len_high, len_low, rest @ ..] = fields else {
+            return Err(invalid("a tagged field is truncated"));
+        };
+        let len = (usize::from(*len_high) << 5) | usize::from(*len_low);
+        if rest.len() < len {
+            return Err(invalid("a tagged field is truncated"));
+        }
+        let (value, rest) = rest.split_at(len);
+        fields = rest;
+        let tag = Fe32::try_from(*tag).expect("tag is a field element");
+
+        // Fields with an unexpected length must be skipped, as must unknown fields.
+        match (tag.to_char(), len) {
+            ('p', HASH_LENGTH) => invoice["payment_hash"] = to_hex(value, "payment hash")?.into(),
+            ('s', HASH_LENGTH) => {
+                invoice["payment_secret"] = to_hex(value, "payment secret")?.into()
+            }
+            ('h', HASH_LENGTH) => {
+                invoice["description_hash"] = to_hex(value, "description hash")?.into();
+            }
+            ('d', _) => {
+                let description = String::from_utf8(bytes(value, "description")?)
+                    .map_err(|_| invalid("the description is not valid UTF-8"))?;
+                invoice["description"] = description.into();
+            }
+            ('n', PUBKEY_LENGTH) => node_id = Some(to_hex(value, "payee node id")?),
+            ('x', _) => invoice["expiry"] = int(value, "expiry")?.into(),
+            ('c', _) => {
+                invoice["min_final_cltv_expiry"] = int(value, "min_final_cltv_expiry")?.into();
+            }
+            ('r', _) => {
+                let route = route_hint(&bytes(value, "route hint")?)?;
+                invoice["route_hints"]
+                    .as_array_mut()
+                    .expect("route_hints is an array")
+                    .push(route);
+            }
+            ('9', _) => invoice["features"] = features(value).into(),
+            _ => {}
+        }
+    }
+
+    if invoice["payment_hash"].is_null() {
+        return Err(invalid("the invoice has no payment hash"));
+    }
+
+    let signature = bytes(signature, "signature")?;
+    let payee = recover_payee(&hrp, data, &signature)?;
+    if node_id.as_ref().is_some_and(|node_id| *node_id != payee) {
+        return Err(invalid("the signature does not match the payee node id"));
+    }
+    invoice["payee"] = payee.into();
+    invoice["signature"] = hex::encode(&signature[..64]).into();
+    invoice["recovery_id"] = signature[64].into();
+
+    Ok(invoice)
+}
+
+/// Splits the `Hrp` of an invoice into its network and amount in millisatoshi.
+fn parse_hrp(hrp: &str) -> Result<(&'static str, Option<u64>), Error> {
+    let prefix = hrp
+        .strip_prefix("ln")
+        .ok_or_else(|| invalid(format!("\"{}\" does not start with \"ln\"", hrp)))?;
+    let (currency, amount) = prefix.split_at(
+        prefix
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(prefix.len()),
+    );
+    let network = match currency {
+        "bc" => "mainnet",
+        "tb" => "testnet",
+        "tbs" => "signet",
+        "bcrt" => "regtest",
+        "sb" => "simnet",
+        _ => return Err(invalid(format!("unknown currency prefix \"{}\"", currency))),
+    };
+
+    Ok((network, parse_amount(amount)?))
+}
+
+/// Converts the amount of an invoice `Hrp`, in bitcoin with an optional multiplier, to
+/// millisatoshi.
+fn parse_amount(amount: &str) -> Result<Option<u64>, Error> {
+    if amount.is_empty() {
+        return Ok(None);
+    }
+    let invalid_amount = || invalid(format!("invalid amount \"{}\"", amount));
+
+    let (digits, multiplier) = match amount.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&amount[..i], Some(c)),
+        _ => (amount, None),
+    };
+    if digits.starts_with('0') || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid_amount());
+    }
+    let value: u64 = digits.parse().map_err(|_| invalid_amount())?;
+
+    let msat = match multiplier {
+        None => value.checked_mul(100_000_000_000),
+        Some('m') => value.checked_mul(100_000_000),
+        Some('u') => value.checked_mul(100_000),
+        Some('n') => value.checked_mul(100),
+        // A pico-bitcoin is a tenth of a millisatoshi.
+        Some('p') if value % 10 == 0 => Some(value / 10),
+        Some(_) => None,
+    };
+    msat.map(Some).ok_or_else(invalid_amount)
+}
+
+/// Recovers the node id that signed the `Hrp` and the data part.
+fn recover_payee(hrp: &str, data: &[u8], signature: &[u8]) -> Result<String, Error> {
+    let mut preimage = hrp.as_bytes().to_vec();
+    preimage.extend(regroup(data, true).expect("padding is added"));
+    let digest: [u8; 32] = Sha256::digest(&preimage).into();
+
+    let invalid_signature = |_| invalid("the signature is invalid");
+    let recovery_id = RecoveryId::from_i32(i32::from(signature[64])).map_err(invalid_signature)?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)
+        .map_err(invalid_signature)?;
+    let payee = Secp256k1::verification_only()
+        .recover_ecdsa(&Message::from_digest(digest), &signature)
+        .map_err(invalid_signature)?;
+
+    Ok(hex::encode(payee.serialize()))
+}
+
+/// Parses the hops of a route hint, the public key, short channel id, fees and CLTV expiry delta
+/// of each channel leading to the payee.
+fn route_hint(route: &[u8]) -> Result<Value, Error> {
+    if route.len() % HOP_LENGTH != 0 {
+        return Err(invalid(format!(
+            "route hints are made of {} byte hops, not {} bytes",
+            HOP_LENGTH,
+            route.len()
+        )));
+    }
+
+    let hops = route
+        .chunks_exact(HOP_LENGTH)
+        .map(|hop| {
+            let short_channel_id = u64::from_be_bytes(hop[33..41].try_into().unwrap());
+            json!({
+                "pubkey": hex::encode(&hop[..33]),
+                "short_channel_id": format!(
+                    "{}x{}x{}",
+                    short_channel_id >> 40,
+                    (short_channel_id >> 16) & 0xffffff,
+                    short_channel_id & 0xffff
+                ),
+                "fee_base_msat": u32::from_be_bytes(hop[41..45].try_into().unwrap()),
+                "fee_proportional_millionths": u32::from_be_bytes(hop[45..49].try_into().unwrap()),
+                "cltv_expiry_delta": u16::from_be_bytes(hop[49..51].try_into().unwrap()),
+            })
+        })
+        .collect();
+    Ok(Value::Array(hops))
+}
+
+/// Returns the numbers of the bits set in a feature field, counted from the last field element.
+fn features(fes: &[u8]) -> Vec<usize> {
+    fes.iter()
+        .rev()
+        .enumerate()
+        .flat_map(|(i, fe)| {
+            (0..5)
+                .filter(move |bit| (fe >> bit) & 1 == 1)
+                .map(move |bit| i * 5 + bit)
+        })
+        .collect()
+}
+
+/// Reads field elements as a big-endian integer.
+fn int(fes: &[u8], name: &str) -> Result<u64, Error> {
+    if fes.len() > 12 {
+        return Err(invalid(format!("the {} does not fit in 64 bits", name)));
+    }
+    Ok(fes.iter().fold(0, |acc, &fe| (acc << 5) | u64::from(fe)))
+}
+
+fn bytes(fes: &[u8], name: &str) -> Result<Vec<u8>, Error> {
+    regroup(fes, false).map_err(|_| invalid(format!("the {} is not padded correctly", name)))
+}
+
+fn to_hex(fes: &[u8], name: &str) -> Result<String, Error> {
+    bytes(fes, name).map(hex::encode)
+}
+
+fn regroup(fes: &[u8], pad: bool) -> Result<Vec<u8>, Error> {
+    let bytes = convert_bits(fes.iter().map(|&fe| i16::from(fe)), 5, 8, pad)?;
+    Ok(bytes.into_iter().map(|b| b as u8).collect())
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::Bolt11 {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    /// The donation example from BOLT11.
+    const DONATION: &str = "lnbc1pvjluezsp5zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zygspp5qqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqqqsyqcyq5rqwzqfqypqdpl2pkx2ctnv5sxxmmwwd5kgetjypeh2ursdae8g6twvus8g6rfwvs8qun0dfjkxaq9qrsgq357wnc5r2ueh7ck6q93dj32dlqnls087fxdwk8qakdyafkq3yap9us6v52vjjsrvywa6rt52cm9r9zqt8r2t7mlcwspyetp5h2tztugp9lfyql";
+
+    /// A testnet invoice with a description hash, expiry, CLTV expiry, route hint and payee node
+    /// id, signed by the key 0x1111…11.
+    const ROUTED: &str = "lntb20m1pvjluezpp5qqqsyqcyq5rqwzqfpg9scrgwpugpzysnzs23v9ccrydpk8qarc0shp58yjmdan79s6qqdhdzgynm4zwqd5d7xmw5fk98klysy043l2ahrqsxqyz5vqcqzysrzjq20q82gphp2nflc7jtzrcazrra7wwgzxqc8u7754cdlpfrmccae92qgzqvzq2ps8pqqqqqqpqqqqq9qqqvnp4qd8n2k7uklxq4aegau7vawtptkgxsja4kt99lpv6krctwpq8tpc65n8qjdk3q89643u3ktpmycwnutq7m0lmsd6fes8xpwr38e2pnvgqj6ppx96s4q9ulclkuhe8rlgrkny5lqy3qahlcrnsqfmcq9uul4fgp9cq06f";
+
+    #[pg_test]
+    fn test_bolt11_decode() {
+        let invoice = bolt11_decode(DONATION).0;
+        assert_eq!(invoice["network"], "mainnet");
+        assert_eq!(invoice["amount_msat"], Value::Null);
+        assert_eq!(invoice["timestamp"], 1496314658);
+        assert_eq!(
+            invoice["payment_hash"],
+            "0001020304050607080900010203040506070809000102030405060708090102"
+        );
+        assert_eq!(
+            invoice["payment_secret"],
+            "1111111111111111111111111111111111111111111111111111111111111111"
+        );
+        assert_eq!(
+            invoice["description"],
+            "Please consider supporting this project"
+        );
+        assert_eq!(invoice["expiry"], 3600);
+        assert_eq!(invoice["features"], json!([8, 14]));
+        assert_eq!(
+            invoice["payee"],
+            "03e7156ae33b0a208d0744199163177e909e80176e55d97a2f221ede0f934dd9ad"
+        );
+    }
+
+    #[pg_test]
+    fn test_bolt11_decode_route_hint() {
+        let invoice = bolt11_decode(ROUTED).0;
+        assert_eq!(invoice["network"], "testnet");
+        assert_eq!(invoice["amount_msat"], 2_000_000_000u64);
+        assert_eq!(
+            invoice["description_hash"],
+            "3925b6f67e2c340036ed12093dd44e0368df1b6ea26c53dbe4811f58fd5db8c1"
+        );
+        assert_eq!(invoice["expiry"], 86400);
+        assert_eq!(invoice["min_final_cltv_expiry"], 144);
+        assert_eq!(
+            invoice["route_hints"],
+            json!([[{
+                "pubkey": "029e03a901b85534ff1e92c43c74431f7ce72046060fcf7a95c37e148f78c77255",
+                "short_channel_id": "66051x263430x1800",
+                "fee_base_msat": 1,
+                "fee_proportional_millionths": 20,
+                "cltv_expiry_delta": 3,
+            }]])
+        );
+        assert_eq!(
+            invoice["payee"],
+            "034f355bdcb7cc0af728ef3cceb9615d90684bb5b2ca5f859ab0f0b704075871aa"
+        );
+    }
+
+    #[pg_test]
+    fn test_parse_amount() {
+        assert_eq!(parse_amount("2500u"), Ok(Some(250_000_000)));
+        assert_eq!(parse_amount("10p"), Ok(Some(1)));
+        assert!(parse_amount("1p").is_err());
+        assert!(parse_amount("025m").is_err());
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bolt11_decode_rejects_bad_checksum() {
+        bolt11_decode(&DONATION.replace("fyql", "fyqq"));
+    }
+}
@@ -0,0 +1,339 @@
+use bech32::NoChecksum;
+use pgrx::prelude::*;
+use pgrx::JsonB;
+use serde_json::{json, Map, Value};
+
+use crate::error::parse_hrp;
+use crate::types::checked;
+use crate::{Error, Variant};
+
+/// The `Hrp`s of offers, invoice requests and invoices.
+const KINDS: &[(&str, &str)] = &[
+    ("lno", "offer"),
+    ("lnr", "invoice_request"),
+    ("lni", "invoice"),
+];
+
+/// How the value of a known TLV record is decoded.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Hex,
+    Utf8,
+    /// A big-endian integer without leading zero bytes.
+    Tu64,
+    /// A list of 32 byte chain hashes.
+    Chains,
+    /// A list of blinded paths.
+    Paths,
+    Features,
+}
+
+/// The known TLV records of offers, invoice requests and invoices.
+const RECORDS: &[(u64, &str, Format)] = &[
+    (0, "invreq_metadata", Format::Hex),
+    (2, "offer_chains", Format::Chains),
+    (4, "offer_metadata", Format::Hex),
+    (6, "offer_currency", Format::Utf8),
+    (8, "offer_amount", Format::Tu64),
+    (10, "offer_description", Format::Utf8),
+    (12, "offer_features", Format::Features),
+    (14, "offer_absolute_expiry", Format::Tu64),
+    (16, "offer_paths", Format::Paths),
+    (18, "offer_issuer", Format::Utf8),
+    (20, "offer_quantity_max", Format::Tu64),
+    (22, "offer_issuer_id", Format::Hex),
+    (80, "invreq_chain", Format::Hex),
+    (82, "invreq_amount", Format::Tu64),
+    (84, "invreq_features", Format::Features),
+    (86, "invreq_quantity", Format::Tu64),
+    (88, "invreq_payer_id", Format::Hex),
+    (89, "invreq_payer_note", Format::Utf8),
+    (90, "invreq_paths", Format::Paths),
+    (160, "invoice_paths", Format::Paths),
+    (162, "invoice_blindedpay", Format::Hex),
+    (164, "invoice_created_at", Format::Tu64),
+    (166, "invoice_relative_expiry", Format::Tu64),
+    (168, "invoice_payment_hash", Format::Hex),
+    (170, "invoice_amount", Format::Tu64),
+    (172, "invoice_fallbacks", Format::Hex),
+    (174, "invoice_features", Format::Features),
+    (176, "invoice_node_id", Format::Hex),
+    (240, "signature", Format::Hex),
+];
+
+/// The length of public keys, and of the short channel id and direction that can replace the
+/// first node id of a blinded path.
+const PUBKEY_LENGTH: usize = 33;
+const SCIDDIR_LENGTH: usize = 9;
+
+/// Decode a BOLT12 offer (`lno`), invoice request (`lnr`) or invoice (`lni`) into a `jsonb`
+/// object.
+///
+/// Segments joined with `+` and optional whitespace are concatenated, and the string is decoded
+/// without a checksum. The object holds the `type`, the raw `tlv` stream in hex, every known TLV
+/// record under its name in the specification, such as `offer_description` or `offer_paths`, and
+/// the remaining records in hex under `unknown`, keyed by type.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bolt12_decode(input: &str) -> JsonB {
+    decode(input).map(JsonB).unwrap_or_else(|e| e.report())
+}
+
+/// Encode a TLV stream as a lowercase BOLT12 string with the `Hrp` `lno`, `lnr` or `lni`, without
+/// a checksum. The stream must be well formed, with its records in ascending order.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bolt12_encode(hrp: &str, tlv: &[u8]) -> String {
+    encode(hrp, tlv).unwrap_or_else(|e| e.report())
+}
+
+fn decode(input: &str) -> Result<Value, Error> {
+    let joined = join_segments(input)?;
+    let (checked, _) = checked(&joined, Some(Variant::NoChecksum))?;
+    let kind = kind(&checked.hrp().to_lowercase())?;
+    let tlv: Vec<u8> = checked.byte_iter().collect();
+
+    let mut decoded = Map::new();
+    let mut unknown = Map::new();
+    decoded.insert("type".into(), kind.into());
+    decoded.insert("tlv".into(), hex::encode(&tlv).into());
+    for (record_type, value) in parse_tlv(&tlv)? {
+        match RECORDS.iter().find(|(known, ..)| *known == record_type) {
+            Some((_, name, format)) => {
+                decoded.insert(name.to_string(), format.decode(value, name)?);
+            }
+            None => {
+                unknown.insert(record_type.to_string(), hex::encode(value).into());
+            }
+        }
+    }
+    decoded.insert("unknown".into(), unknown.into());
+
+    Ok(decoded.into())
+}
+
+fn encode(hrp: &str, tlv: &[u8]) -> Result<String, Error> {
+    kind(&hrp.to_ascii_lowercase())?;
+    parse_tlv(tlv)?;
+    Ok(bech32::encode_lower::<NoChecksum>(parse_hrp(hrp)?, tlv)?)
+}
+
+/// Joins the segments of a string split with `+` followed by optional whitespace, which BOLT12
+/// allows to break long strings.
+fn join_segments(input: &str) -> Result<String, Error> {
+    let mut segments = input.trim().split('+');
+    let mut joined = segments.next().unwrap_or_default().to_string();
+    for segment in segments {
+        let segment = segment.trim_start();
+        if joined.is_empty() || joined.ends_with(char::is_whitespace) || segment.is_empty() {
+            return Err(invalid("a \"+\" must join two segments of the string"));
+        }
+        joined.push_str(segment);
+    }
+    Ok(joined)
+}
+
+fn kind(hrp: &str) -> Result<&'static str, Error> {
+    KINDS
+        .iter()
+        .find(|(prefix, _)| *prefix == hrp)
+        .map(|(_, kind)| *kind)
+        .ok_or_else(|| invalid(format!("unknown prefix \"{}\"", hrp)))
+}
+
+/// Splits a TLV stream into its records, which must have ascending types.
+fn parse_tlv(mut tlv: &[u8]) -> Result<Vec<(u64, &[u8])>, Error> {
+    let mut records = Vec::new();
+    let mut previous = None;
+
+    while !tlv.is_empty() {
+        let record_type = big_size(&mut tlv)?;
+        if previous.is_some_and(|previous| record_type <= previous) {
+            return Err(invalid("the TLV records are not in ascending order"));
+        }
+        let len = big_size(&mut tlv)?;
+        let value = usize::try_from(len)
+            .ok()
+            .and_then(|len| tlv.get(..len))
+            .ok_or_else(|| invalid(format!("TLV record {} is truncated", record_type)))?;
+        tlv = &tlv[value.len()..];
+        records.push((record_type, value));
+        previous = Some(record_type);
+    }
+
+    Ok(records)
+}
+
+/// Reads a BigSize integer, which must be minimally encoded.
+fn big_size(data: &mut &[u8]) -> Result<u64, Error> {
+    let truncated = || invalid("the TLV stream is truncated");
+    let (&first, rest) = data.split_first().ok_or_else(truncated)?;
+    let (len, min) = match first {
+        0xfd => (2, 0xfd),
+        0xfe => (4, 0x1_0000),
+        0xff => (8, 0x1_0000_0000),
+        _ => {
+            *data = rest;
+            return Ok(u64::from(first));
+        }
+    };
+
+    let value = rest
+        .get(..len)
+        .ok_or_else(truncated)?
+        .iter()
+        .fold(0, |acc, &b| (acc << 8) | u64::from(b));
+    if value < min {
+        return Err(invalid("the TLV stream is not minimally encoded"));
+    }
+    *data = &rest[len..];
+    Ok(value)
+}
+
+impl Format {
+    fn decode(self, value: &[u8], name: &str) -> Result<Value, Error> {
+        match self {
+            Format::Hex => Ok(hex::encode(value).into()),
+            Format::Utf8 => String::from_utf8(value.to_vec())
+                .map(Value::from)
+                .map_err(|_| invalid(format!("{} is not valid UTF-8", name))),
+            Format::Tu64 => {
+                if value.len() > 8 || value.first() == Some(&0) {
+                    return Err(invalid(format!("{} is not minimally encoded", name)));
+                }
+                Ok(value
+                    .iter()
+                    .fold(0, |acc, &b| (acc << 8) | u64::from(b))
+                    .into())
+            }
+            Format::Chains => {
+                if value.len() % 32 != 0 {
+                    return Err(invalid(format!("{} is not a list of chain hashes", name)));
+                }
+                Ok(value.chunks_exact(32).map(hex::encode).collect())
+            }
+            Format::Paths => blinded_paths(value),
+            Format::Features => Ok(value
+                .iter()
+                .rev()
+                .enumerate()
+                .flat_map(|(i, byte)| {
+                    (0..8)
+                        .filter(move |bit| (byte >> bit) & 1 == 1)
+                        .map(move |bit| i * 8 + bit)
+                })
+                .collect()),
+        }
+    }
+}
+
+/// Parses a list of blinded paths: the first node id, the key of the first hop, and the blinded
+/// node id and encrypted data of each hop.
+fn blinded_paths(mut data: &[u8]) -> Result<Value, Error> {
+    let mut paths = Vec::new();
+
+    while !data.is_empty() {
+        // The first node can be given by a short channel id and direction instead of its key.
+        let first_node_id = match data[0] {
+            0 | 1 => take(&mut data, SCIDDIR_LENGTH)?,
+            _ => take(&mut data, PUBKEY_LENGTH)?,
+        };
+        let first_path_key = take(&mut data, PUBKEY_LENGTH)?;
+        let num_hops = take(&mut data, 1)?[0];
+
+        let mut hops = Vec::new();
+        for _ in 0..num_hops {
+            let blinded_node_id = take(&mut data, PUBKEY_LENGTH)?;
+            let len = take(&mut data, 2)?;
+            let encrypted_data =
+                take(&mut data, usize::from(u16::from_be_bytes([len[0], len[1]])))?;
+            hops.push(json!({
+                "blinded_node_id": hex::encode(blinded_node_id),
+                "encrypted_recipient_data": hex::encode(encrypted_data),
+            }));
+        }
+
+        paths.push(json!({
+            "first_node_id": hex::encode(first_node_id),
+            "first_path_key": hex::encode(first_path_key),
+            "hops": hops,
+        }));
+    }
+
+    Ok(paths.into())
+}
+
+fn take<'a>(data: &mut &'a [u8], len: usize) -> Result<&'a [u8], Error> {
+    if data.len() < len {
+        return Err(invalid("a blinded path is truncated"));
+    }
+    let (value, rest) = data.split_at(len);
+    *data = rest;
+    Ok(value)
+}
+
+fn invalid(reason: impl Into<String>) -> Error {
+    Error::Bolt12 {
+        reason: reason.into(),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const ISSUER_ID: &str = "02eec7245d6b7d2ccb30380bfbe2a3648cd7a942653f5aa340edcea1f283686619";
+
+    /// The minimal offer from the BOLT12 test vectors.
+    const OFFER: &str =
+        "lno1pgx9getnwss8vetrw3hhyuckyypwa3eyt44h6txtxquqh7lz5djge4afgfjn7k4rgrkuag0jsd5xvxg";
+
+    /// An offer with an amount, a blinded path and an issuer.
+    const OFFER_WITH_PATH: &str = "lno1pqqnyzsv23jhxapqwejkxar0wfe3q6gzamrjghtt05kvkvpcp0a79gmy3nt6jsn98ad2xs8de6sl9qmgvcvsyvenxvenxvenxvenxvenxvenxvenxvenxvenxvenxvenxvenxvenqyp5g3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3zyg3qqqw4thnqjq4skc6trv5tzzqhwcuj966ma9n9nqwqtl032xeyv6755yeflt235pmww58egx6rxry";
+
+    #[pg_test]
+    fn test_bolt12_decode() {
+        let offer = bolt12_decode(OFFER).0;
+        assert_eq!(offer["type"], "offer");
+        assert_eq!(offer["offer_description"], "Test vectors");
+        assert_eq!(offer["offer_issuer_id"], ISSUER_ID);
+        assert_eq!(offer["unknown"], json!({}));
+    }
+
+    #[pg_test]
+    fn test_bolt12_decode_continuations() {
+        let (head, tail) = OFFER_WITH_PATH.split_at(100);
+        let offer = bolt12_decode(&format!("{}+\n  {}", head, tail)).0;
+        assert_eq!(offer["offer_amount"], 50);
+        assert_eq!(offer["offer_issuer"], "alice");
+        assert_eq!(
+            offer["offer_paths"],
+            json!([{
+                "first_node_id": ISSUER_ID,
+                "first_path_key": format!("02{}", "33".repeat(32)),
+                "hops": [{
+                    "blinded_node_id": format!("03{}", "44".repeat(32)),
+                    "encrypted_recipient_data": "aabbcc",
+                }],
+            }])
+        );
+    }
+
+    #[pg_test]
+    fn test_bolt12_roundtrip() {
+        let offer = bolt12_decode(OFFER_WITH_PATH).0;
+        let tlv = hex::decode(offer["tlv"].as_str().unwrap()).unwrap();
+        assert_eq!(bolt12_encode("lno", &tlv), OFFER_WITH_PATH);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid BOLT12 string")]
+    fn test_bolt12_decode_rejects_trailing_plus() {
+        bolt12_decode(&format!("{}+", OFFER));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid BOLT12 string")]
+    fn test_bolt12_encode_rejects_unordered_records() {
+        bolt12_encode("lno", &[10, 0, 8, 0]);
+    }
+}
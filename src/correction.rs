@@ -0,0 +1,312 @@
+use bech32::primitives::decode::{
+    CharError, ChecksumError, UncheckedHrpstring, UncheckedHrpstringError,
+};
+use bech32::Hrp;
+use pgrx::prelude::*;
+use std::collections::HashMap;
+
+use crate::Error;
+
+/// The characters allowed in the data part of a bech32 string, indexed by their value.
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// The generator of the BCH code behind both checksums.
+const GENERATOR: [u32; 5] = [0x3b6a57b2, 0x26508e6d, 0x1ea119fa, 0x3d4233dd, 0x2a1462b3];
+
+/// The residues of a valid bech32 and a valid bech32m string.
+const RESIDUES: [u32; 2] = [0x1, 0x2bc830a3];
+
+/// The number of data characters taken by the checksum.
+const CHECKSUM_LENGTH: usize = 6;
+
+/// Both checksums detect up to 4 substitutions, so up to 2 can be corrected unambiguously.
+const MAX_ERRORS: usize = 2;
+
+/// A set of substitutions, as the index into the data part and the value xored into it.
+type Correction = Vec<(usize, u8)>;
+
+/// A bech32 string that might contain substitution errors in its data part.
+struct Received {
+    hrp: String,
+    /// The values of the data characters, with invalid characters replaced by 0.
+    data: Vec<u8>,
+    /// Indices of invalid data characters, which must be part of every correction.
+    erasures: Vec<usize>,
+    /// The 0-based character position of the first data character in the input.
+    offset: usize,
+    uppercase: bool,
+}
+
+impl Received {
+    fn parse(input: &str) -> Result<Self, Error> {
+        match UncheckedHrpstring::new(input) {
+            Err(UncheckedHrpstringError::Char(CharError::InvalidChar(_))) | Ok(_) => {}
+            Err(e) => return Err(Error::parse(input, e)),
+        }
+        let has_upper = input.chars().any(|c| c.is_ascii_uppercase());
+        if has_upper && input.chars().any(|c| c.is_ascii_lowercase()) {
+            let e = UncheckedHrpstringError::Char(CharError::MixedCase);
+            return Err(Error::parse(input, e));
+        }
+
+        // Parsing stops at the first invalid character, before the separator and `Hrp` are checked.
+        let lowercase = input.to_ascii_lowercase();
+        let separator = lowercase.rfind('1').ok_or_else(|| {
+            Error::parse(
+                input,
+                UncheckedHrpstringError::Char(CharError::MissingSeparator),
+            )
+        })?;
+        let (hrp, data) = (&lowercase[..separator], &lowercase[separator + 1..]);
+        Hrp::parse(hrp).map_err(|e| Error::parse(input, UncheckedHrpstringError::Hrp(e)))?;
+        if data.chars().count() < CHECKSUM_LENGTH {
+            return Err(Error::checksum(input, ChecksumError::InvalidLength, None));
+        }
+
+        let mut erasures = Vec::new();
+        let data = data
+            .chars()
+            .enumerate()
+            .map(|(i, c)| {
+                CHARSET
+                    .iter()
+                    .position(|&b| char::from(b) == c)
+                    .map(|value| value as u8)
+                    .unwrap_or_else(|| {
+                        erasures.push(i);
+                        0
+                    })
+            })
+            .collect();
+
+        Ok(Received {
+            offset: hrp.chars().count() + 1,
+            hrp: hrp.to_string(),
+            data,
+            erasures,
+            uppercase: has_upper,
+        })
+    }
+
+    fn residue(&self) -> u32 {
+        let hrp = self.hrp.bytes();
+        hrp.clone()
+            .map(|b| b >> 5)
+            .chain([0])
+            .chain(hrp.map(|b| b & 0x1f))
+            .chain(self.data.iter().copied())
+            .fold(1, polymod_step)
+    }
+
+    /// Returns, for every data index, the change in residue caused by flipping each bit of the
+    /// value at that index. The checksum is linear, so the change caused by any substitution is the
+    /// xor of these.
+    fn error_basis(&self) -> Vec<[u32; 5]> {
+        let mut basis = vec![[0; 5]; self.data.len()];
+        let mut current: [u32; 5] = core::array::from_fn(|bit| 1 << bit);
+        for index in (0..self.data.len()).rev() {
+            basis[index] = current;
+            current = current.map(|c| polymod_step(c, 0));
+        }
+        basis
+    }
+
+    /// Returns every correction of at most `max_errors` substitutions that results in a valid
+    /// bech32 or bech32m string, with the fewest substitutions first.
+    fn corrections(&self, max_errors: usize) -> Vec<Correction> {
+        let basis = self.error_basis();
+        let syndrome = |index: usize, error: u8| -> u32 {
+            (0..5)
+                .filter(|bit| (error >> bit) & 1 == 1)
+                .fold(0, |acc, bit| acc ^ basis[index][bit])
+        };
+
+        // Erased characters may take any value, others must change.
+        let mut singles: HashMap<u32, Vec<(usize, u8)>> = HashMap::new();
+        for index in 0..self.data.len() {
+            let min_error = if self.erasures.contains(&index) { 0 } else { 1 };
+            for error in min_error..32 {
+                singles
+                    .entry(syndrome(index, error))
+                    .or_default()
+                    .push((index, error));
+            }
+        }
+
+        let mut corrections = Vec::new();
+        let residue = self.residue();
+        for target in RESIDUES.map(|valid| residue ^ valid) {
+            let mut candidates: Vec<Correction> = Vec::new();
+            if target == 0 {
+                candidates.push(Vec::new());
+            }
+            if max_errors >= 1 {
+                candidates.extend(singles.get(&target).into_iter().flatten().map(|&e| vec![e]));
+            }
+            if max_errors >= 2 {
+                for (&first_syndrome, firsts) in &singles {
+                    let Some(seconds) = singles.get(&(target ^ first_syndrome)) else {
+                        continue;
+                    };
+                    for &first in firsts {
+                        for &second in seconds.iter().filter(|second| second.0 > first.0) {
+                            candidates.push(vec![first, second]);
+                        }
+                    }
+                }
+            }
+
+            corrections.extend(candidates.into_iter().filter(|correction| {
+                self.erasures
+                    .iter()
+                    .all(|erasure| correction.iter().any(|(index, _)| index == erasure))
+            }));
+        }
+
+        corrections.sort();
+        corrections.sort_by_key(Vec::len);
+        corrections.dedup();
+        corrections
+    }
+
+    /// Returns the 1-based character positions of the substitutions in `correction`.
+    fn positions(&self, correction: &Correction) -> Vec<i32> {
+        correction
+            .iter()
+            .map(|(index, _)| (self.offset + index + 1) as i32)
+            .collect()
+    }
+
+    /// Returns the string with the substitutions of `correction` applied.
+    fn apply(&self, correction: &Correction) -> String {
+        let mut data = self.data.clone();
+        for &(index, error) in correction {
+            data[index] ^= error;
+        }
+
+        let corrected: String = self
+            .hrp
+            .chars()
+            .chain(['1'])
+            .chain(
+                data.iter()
+                    .map(|&value| char::from(CHARSET[value as usize])),
+            )
+            .collect();
+        match self.uppercase {
+            true => corrected.to_ascii_uppercase(),
+            false => corrected,
+        }
+    }
+}
+
+fn polymod_step(residue: u32, value: u8) -> u32 {
+    let top = residue >> 25;
+    let residue = ((residue & 0x1ffffff) << 5) ^ u32::from(value);
+    (0..5)
+        .filter(|bit| (top >> bit) & 1 == 1)
+        .fold(residue, |residue, bit| residue ^ GENERATOR[bit])
+}
+
+/// Locate mistyped characters in a bech32 string from its checksum.
+///
+/// Returns the 1-based character positions of up to 2 substituted characters in the data part, an
+/// empty array for a valid string, and `NULL` if the errors cannot be located unambiguously.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_locate_errors(input: &str) -> Option<Vec<i32>> {
+    let received = Received::parse(input).unwrap_or_else(|e| e.report());
+    let corrections = received.corrections(MAX_ERRORS);
+
+    match corrections.as_slice() {
+        [only] => Some(received.positions(only)),
+        [first, second, ..] if first.len() < second.len() => Some(received.positions(first)),
+        _ => None,
+    }
+}
+
+/// Suggest valid bech32 or bech32m strings that differ from `input` in at most `max_errors`
+/// characters of the data part, with the fewest changes first. `max_errors` is at most 2.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_suggest_corrections(input: &str, max_errors: i32) -> SetOfIterator<'static, String> {
+    let max_errors = usize::try_from(max_errors)
+        .ok()
+        .filter(|&max_errors| max_errors <= MAX_ERRORS)
+        .unwrap_or_else(|| {
+            Error::Correction {
+                reason: format!("max_errors must be between 0 and {}", MAX_ERRORS),
+            }
+            .report()
+        });
+    let received = Received::parse(input).unwrap_or_else(|e| e.report());
+
+    let suggestions: Vec<String> = received
+        .corrections(max_errors)
+        .iter()
+        .map(|correction| received.apply(correction))
+        .collect();
+    SetOfIterator::new(suggestions)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+
+    #[pg_test]
+    fn test_bech32_locate_errors_valid() {
+        assert_eq!(bech32_locate_errors(ADDRESS), Some(vec![]));
+    }
+
+    #[pg_test]
+    fn test_bech32_locate_errors() {
+        // "4" at position 7 mistyped as "5", and "x" at position 40 as "z".
+        let input = "union15qemq0vw6y3gc3u3e0aty2e764u4gs5lnzk4rv";
+        assert_eq!(bech32_locate_errors(input), Some(vec![7, 40]));
+    }
+
+    #[pg_test]
+    fn test_bech32_locate_errors_invalid_char() {
+        // "x" at position 40 mistyped as "b", which is not a bech32 character.
+        let input = "UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNBK4RV";
+        assert_eq!(bech32_locate_errors(input), Some(vec![40]));
+    }
+
+    #[pg_test]
+    fn test_bech32_suggest_corrections() {
+        let input = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnzk4rv";
+        let suggestions: Vec<String> = bech32_suggest_corrections(input, 1).collect();
+        assert_eq!(suggestions, vec![ADDRESS.to_string()]);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot correct bech32 string")]
+    fn test_bech32_suggest_corrections_rejects_max_errors() {
+        bech32_suggest_corrections(ADDRESS, 3);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_locate_errors_rejects_missing_separator() {
+        bech32_locate_errors("hello");
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_suggest_corrections_rejects_missing_separator() {
+        bech32_suggest_corrections("hello", 1);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_locate_errors_rejects_empty_hrp() {
+        bech32_locate_errors("1qemq0vw6y3gc3b");
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_locate_errors_rejects_short_data() {
+        bech32_locate_errors("union1qemqb");
+    }
+}
@@ -0,0 +1,651 @@
+use bech32::primitives::decode::{
+    CharError, ChecksumError, SegwitHrpstringError, UncheckedHrpstring, UncheckedHrpstringError,
+};
+use bech32::primitives::hrp;
+use bech32::segwit::VERSION_0;
+use bech32::{DecodeError, EncodeError};
+use core::ffi::{c_int, CStr};
+use core::str::Utf8Error;
+use std::ffi::CString;
+
+use pgrx::prelude::*;
+
+use crate::{secret, Variant};
+
+/// The characters allowed in the data part of a bech32 string.
+const CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// `22Z01`, an implementation-defined code in the `data_exception` class for a checksum mismatch.
+const ERRCODE_CHECKSUM_MISMATCH: c_int = make_sqlstate(*b"22Z01");
+
+/// Failures surfaced to Postgres through `ereport`.
+///
+/// Every variant maps to a specific SQLSTATE, so callers can tell malformed user input apart from
+/// an internal error in the extension:
+/// - `22P02` (`invalid_text_representation`) for malformed input.
+/// - `22Z01` for a checksum mismatch.
+/// - `22004` (`null_value_not_allowed`) for a `NULL` element in an array argument.
+/// - `22023` (`invalid_parameter_value`) for an unknown mode or an input that cannot be encoded.
+/// - `23514` (`check_violation`) for a value that does not match the type modifier of a column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// The input is not a well formed bech32 string.
+    Malformed {
+        input: String,
+        reason: String,
+        position: Option<usize>,
+    },
+    /// The input is well formed, but the checksum does not match the data.
+    Checksum {
+        input: String,
+        expected: Option<Variant>,
+    },
+    /// The `Hrp` (Human Readable Part) passed to an encoder is invalid.
+    Hrp { hrp: String, error: hrp::Error },
+    /// The encoded string would exceed the maximum length of the checksum algorithm.
+    TooLong {
+        encoded_length: usize,
+        code_length: usize,
+    },
+    /// A value passed to a field element encoder is not a 5-bit value.
+    Fe32(i16),
+    /// An array argument contains a `NULL` element.
+    NullElement { argument: String },
+    /// The arguments of a bit regrouping are invalid, or the input is not padded correctly.
+    ConvertBits { reason: String },
+    /// The input does not have the `Hrp` the caller expected.
+    UnexpectedHrp {
+        input: String,
+        expected: String,
+        found: String,
+    },
+    /// The requested mode is not one of `bech32`, `bech32m` or `nochecksum`.
+    UnknownMode(String),
+    /// The witness version or program passed to a segwit encoder is invalid.
+    Segwit { reason: String },
+    /// The requested network is not one of the Bitcoin networks with a segwit `Hrp`.
+    UnknownNetwork(String),
+    /// A BOLT11 invoice is malformed or its signature is invalid.
+    Bolt11 { reason: String },
+    /// A BOLT12 string or its TLV stream is malformed.
+    Bolt12 { reason: String },
+    /// An LNURL does not wrap a UTF-8 URL, or the URL passed to the encoder is empty.
+    Lnurl { reason: String },
+    /// The payload of a NIP-19 entity, or the fields passed to a NIP-19 encoder, are invalid.
+    Nostr { reason: String },
+    /// The arguments of a correction search are invalid.
+    Correction { reason: String },
+    /// The string cannot be rendered as a QR code.
+    Qr { reason: String },
+    /// The type modifier of `bech32('hrp', 'variant')` is invalid.
+    Typmod { reason: String },
+    /// The value does not have the `Hrp` or checksum variant required by the type modifier.
+    TypmodMismatch {
+        value: String,
+        typmod: String,
+        reason: String,
+    },
+}
+
+impl Error {
+    /// Classifies a [`DecodeError`] for `input`.
+    pub fn decode(input: &str, error: DecodeError) -> Self {
+        match error {
+            DecodeError::Parse(e) => Error::parse(input, e),
+            DecodeError::Checksum(e) => Error::checksum(input, e, None),
+            e => Error::malformed(input, root_cause(&e), None),
+        }
+    }
+
+    /// Classifies an [`UncheckedHrpstringError`] for `input`.
+    pub fn parse(input: &str, error: UncheckedHrpstringError) -> Self {
+        let position = match &error {
+            UncheckedHrpstringError::Char(CharError::InvalidChar(c)) => {
+                // The data part is checked from the separator on, and the first invalid character
+                // is reported.
+                let data = input.rfind('1').map_or(0, |i| i + 1);
+                input[data..]
+                    .find(*c)
+                    .map(|i| char_position(input, data + i))
+            }
+            UncheckedHrpstringError::Char(CharError::MixedCase)
+            | UncheckedHrpstringError::Hrp(hrp::Error::MixedCase) => mixed_case_position(input),
+            UncheckedHrpstringError::Hrp(hrp::Error::NonAsciiChar(c)) => {
+                input.find(*c).map(|i| char_position(input, i))
+            }
+            UncheckedHrpstringError::Hrp(hrp::Error::InvalidAsciiByte(b)) => {
+                input.find(char::from(*b)).map(|i| char_position(input, i))
+            }
+            _ => None,
+        };
+
+        Error::malformed(input, root_cause(&error), position)
+    }
+
+    /// Classifies an `input` that is not valid UTF-8, as can be passed to the input function in
+    /// a database with another encoding.
+    pub fn utf8(input: &CStr, error: Utf8Error) -> Self {
+        let input = input.to_string_lossy();
+        let position = char_position(&input, error.valid_up_to());
+        Error::malformed(
+            &input,
+            "the string is not valid UTF-8".to_string(),
+            Some(position),
+        )
+    }
+
+    /// Classifies a [`ChecksumError`] for `input`, which was checked against the `expected`
+    /// checksum algorithm, or against both bech32 and bech32m if `None`.
+    pub fn checksum(input: &str, error: ChecksumError, expected: Option<Variant>) -> Self {
+        match error {
+            ChecksumError::InvalidResidue => Error::Checksum {
+                input: input.to_string(),
+                expected,
+            },
+            e => Error::malformed(input, root_cause(&e), None),
+        }
+    }
+
+    /// Classifies a [`SegwitHrpstringError`] for `input`.
+    pub fn segwit(input: &str, error: SegwitHrpstringError) -> Self {
+        match error {
+            SegwitHrpstringError::Unchecked(e) => Error::parse(input, e),
+            SegwitHrpstringError::Checksum(e) => {
+                // BIP-350: version 0 uses bech32, every later version uses bech32m.
+                let expected = UncheckedHrpstring::new(input)
+                    .ok()
+                    .and_then(|unchecked| unchecked.witness_version())
+                    .map(|version| match version {
+                        VERSION_0 => Variant::Bech32,
+                        _ => Variant::Bech32m,
+                    });
+                Error::checksum(input, e, expected)
+            }
+            e => Error::malformed(input, root_cause(&e), None),
+        }
+    }
+
+    fn malformed(input: &str, reason: String, position: Option<usize>) -> Self {
+        Error::Malformed {
+            input: input.to_string(),
+            reason,
+            position,
+        }
+    }
+
+    fn sqlstate(&self) -> c_int {
+        let code = match self {
+            Error::Malformed { .. } | Error::Hrp { .. } => {
+                PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION
+            }
+            Error::Checksum { .. } => return ERRCODE_CHECKSUM_MISMATCH,
+            Error::NullElement { .. } => PgSqlErrorCode::ERRCODE_NULL_VALUE_NOT_ALLOWED,
+            Error::TooLong { .. }
+            | Error::UnknownMode(_)
+            | Error::Segwit { .. }
+            | Error::UnknownNetwork(_)
+            | Error::Fe32(_)
+            | Error::ConvertBits { .. }
+            | Error::Nostr { .. }
+            | Error::Bolt11 { .. }
+            | Error::Bolt12 { .. }
+            | Error::Lnurl { .. }
+            | Error::UnexpectedHrp { .. }
+            | Error::Correction { .. }
+            | Error::Qr { .. }
+            | Error::Typmod { .. } => PgSqlErrorCode::ERRCODE_INVALID_PARAMETER_VALUE,
+            Error::TypmodMismatch { .. } => PgSqlErrorCode::ERRCODE_CHECK_VIOLATION,
+        };
+        code as c_int
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Error::Malformed { input, .. } => {
+                format!("invalid input syntax for type bech32: \"{}\"", input)
+            }
+            Error::Checksum { input, .. } => {
+                format!("invalid checksum in bech32 string \"{}\"", input)
+            }
+            Error::Hrp { hrp, .. } => format!("invalid bech32 human-readable part: \"{}\"", hrp),
+            Error::TooLong { .. } => "bech32 encoded string is too long".to_string(),
+            Error::UnknownMode(mode) => format!("unknown bech32 mode: \"{}\"", mode),
+            Error::Segwit { .. } => "cannot encode segwit address".to_string(),
+            Error::UnknownNetwork(network) => format!("unknown bitcoin network: \"{}\"", network),
+            Error::Fe32(value) => format!("invalid bech32 field element: {}", value),
+            Error::NullElement { argument } => {
+                format!("array argument \"{}\" must not contain nulls", argument)
+            }
+            Error::ConvertBits { .. } => "cannot convert bits".to_string(),
+            Error::Nostr { .. } => "invalid nostr entity".to_string(),
+            Error::Bolt11 { .. } => "invalid BOLT11 invoice".to_string(),
+            Error::Bolt12 { .. } => "invalid BOLT12 string".to_string(),
+            Error::Lnurl { .. } => "invalid LNURL".to_string(),
+            Error::UnexpectedHrp {
+                input, expected, ..
+            } => format!(
+                "bech32 string \"{}\" does not have the human-readable part \"{}\"",
+                input, expected
+            ),
+            Error::Correction { .. } => "cannot correct bech32 string".to_string(),
+            Error::Qr { .. } => "cannot render QR code".to_string(),
+            Error::Typmod { .. } => "invalid type modifier for type bech32".to_string(),
+            Error::TypmodMismatch { value, typmod, .. } => {
+                format!("value \"{}\" does not match type bech32{}", value, typmod)
+            }
+        }
+    }
+
+    fn detail(&self) -> Option<String> {
+        match self {
+            Error::Malformed {
+                reason,
+                position: Some(position),
+                ..
+            } => Some(format!(
+                "{} at character position {}.",
+                capitalize(reason),
+                position
+            )),
+            Error::Malformed { reason, .. } => Some(format!("{}.", capitalize(reason))),
+            Error::Checksum {
+                expected: Some(expected),
+                ..
+            } => Some(format!(
+                "The string does not have a valid {} checksum.",
+                expected.as_str()
+            )),
+            Error::Checksum { .. } => {
+                Some("The string has neither a valid bech32 nor a valid bech32m checksum.".into())
+            }
+            Error::Hrp { error, .. } => Some(format!("{}.", capitalize(&error.to_string()))),
+            Error::TooLong {
+                encoded_length,
+                code_length,
+            } => Some(format!(
+                "The encoded string would be {} characters long, the maximum is {}.",
+                encoded_length, code_length
+            )),
+            Error::UnexpectedHrp { found, .. } => {
+                Some(format!("The human-readable part is \"{}\".", found))
+            }
+            Error::Segwit { reason }
+            | Error::ConvertBits { reason }
+            | Error::Nostr { reason }
+            | Error::Bolt11 { reason }
+            | Error::Bolt12 { reason }
+            | Error::Lnurl { reason }
+            | Error::Correction { reason }
+            | Error::Qr { reason }
+            | Error::Typmod { reason }
+            | Error::TypmodMismatch { reason, .. } => Some(format!("{}.", capitalize(reason))),
+            Error::Fe32(_)
+            | Error::NullElement { .. }
+            | Error::UnknownMode(_)
+            | Error::UnknownNetwork(_) => None,
+        }
+    }
+
+    fn hint(&self) -> Option<String> {
+        match self {
+            Error::Malformed {
+                position: Some(_), ..
+            } => Some(format!(
+                "Bech32 strings consist of a human-readable part, the separator \"1\" and data \
+                 characters from \"{}\", in a single case.",
+                CHARSET
+            )),
+            Error::Malformed { .. } => Some(
+                "Bech32 strings consist of a human-readable part, the separator \"1\" and at \
+                 least 6 data characters."
+                    .into(),
+            ),
+            Error::Checksum { .. } => {
+                Some("Check the string for mistyped or swapped characters.".into())
+            }
+            Error::Hrp { .. } => Some(
+                "The human-readable part must be 1 to 83 printable US-ASCII characters in a \
+                 single case."
+                    .into(),
+            ),
+            Error::TooLong { .. } => Some("Shorten the human-readable part or the input.".into()),
+            Error::Fe32(_) => Some("Field elements are 5-bit values from 0 to 31.".into()),
+            Error::UnknownMode(_) => {
+                Some("Supported modes are \"bech32\", \"bech32m\" and \"nochecksum\".".into())
+            }
+            Error::Segwit { .. } => Some(
+                "Witness versions range from 0 to 16. Version 0 programs are 20 or 32 bytes long, \
+                 later versions 2 to 40 bytes."
+                    .into(),
+            ),
+            Error::UnknownNetwork(_) => Some(
+                "Supported networks are \"mainnet\" (bc), \"testnet\" and \"signet\" (tb) and \
+                 \"regtest\" (bcrt)."
+                    .into(),
+            ),
+            Error::Nostr { .. } => Some(
+                "NIP-19 entities are \"npub\", \"nsec\", \"note\", \"nprofile\", \"nevent\" and \
+                 \"naddr\", with 32 byte keys and event ids."
+                    .into(),
+            ),
+            Error::Qr { .. } => Some(
+                "Error correction levels are \"L\", \"M\", \"Q\" and \"H\". Lower levels fit \
+                 longer strings."
+                    .into(),
+            ),
+            Error::Typmod { .. } => Some(
+                "Use bech32('hrp') or bech32('hrp', 'variant'), where the human-readable part is 1 \
+                 to 83 printable US-ASCII characters in a single case and the variant is \
+                 \"bech32\", \"bech32m\" or \"nochecksum\"."
+                    .into(),
+            ),
+            Error::UnexpectedHrp { .. }
+            | Error::ConvertBits { .. }
+            | Error::Bolt11 { .. }
+            | Error::Bolt12 { .. }
+            | Error::Lnurl { .. }
+            | Error::Correction { .. }
+            | Error::NullElement { .. }
+            | Error::TypmodMismatch { .. } => None,
+        }
+    }
+
+    /// Replaces the input in this error with a placeholder, for a string with the secret `hrp`.
+    fn redact(self, hrp: &str) -> Self {
+        let redacted = format!("{}1[redacted]", hrp);
+        match self {
+            Error::Malformed { position, .. } => Error::Malformed {
+                input: redacted,
+                reason: "the string is malformed".to_string(),
+                position,
+            },
+            Error::Checksum { expected, .. } => Error::Checksum {
+                input: redacted,
+                expected,
+            },
+            Error::UnexpectedHrp {
+                expected, found, ..
+            } => Error::UnexpectedHrp {
+                input: redacted,
+                expected,
+                found,
+            },
+            Error::TypmodMismatch { typmod, reason, .. } => Error::TypmodMismatch {
+                value: redacted,
+                typmod,
+                reason,
+            },
+            e => e,
+        }
+    }
+
+    /// Returns the string this error is about, if it includes it.
+    fn input(&self) -> Option<&str> {
+        match self {
+            Error::Malformed { input, .. }
+            | Error::Checksum { input, .. }
+            | Error::UnexpectedHrp { input, .. } => Some(input),
+            Error::TypmodMismatch { value, .. } => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Raises this error as a Postgres `ERROR`, aborting the current transaction.
+    ///
+    /// Errors about a string with one of the `pg_bech32.secret_hrps` are redacted, and neither the
+    /// statement nor the context, where PL/pgSQL puts the statement text, is logged with them.
+    pub fn report(self) -> ! {
+        let (error, secret) = match self.input().and_then(secret::secret_hrp) {
+            Some(hrp) => (self.redact(&hrp), true),
+            None => (self, false),
+        };
+
+        // pgrx's `ErrorReport` only takes the SQLSTATEs Postgres defines, so the report is built
+        // with the `ereport` functions directly.
+        let sqlstate = error.sqlstate();
+        let message = to_cstring(error.message());
+        let detail = error.detail().map(to_cstring);
+        let hint = error.hint().map(to_cstring);
+
+        // SAFETY: `errfinish` does not return for an `ERROR`. The FFI guard turns its longjmp into
+        // a Rust panic, which drops the strings above, and pgrx rethrows the error unchanged.
+        unsafe {
+            pg_sys::ffi::pg_guard_ffi_boundary(|| {
+                ereport::start();
+                ereport::errcode(sqlstate);
+                ereport::errmsg(c"%s".as_ptr(), message.as_ptr());
+                if let Some(detail) = &detail {
+                    ereport::errdetail(c"%s".as_ptr(), detail.as_ptr());
+                }
+                if let Some(hint) = &hint {
+                    ereport::errhint(c"%s".as_ptr(), hint.as_ptr());
+                }
+                if secret {
+                    ereport::errhidestmt(true);
+                    ereport::errhidecontext(true);
+                }
+                ereport::finish();
+            })
+        }
+        unreachable!("ereport returned for an ERROR")
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(e: EncodeError) -> Self {
+        match e {
+            EncodeError::TooLong(e) => Error::TooLong {
+                encoded_length: e.encoded_length,
+                code_length: e.code_length,
+            },
+            e => unreachable!("encoding into a String failed: {}", e),
+        }
+    }
+}
+
+impl From<bech32::segwit::EncodeError> for Error {
+    fn from(e: bech32::segwit::EncodeError) -> Self {
+        Error::Segwit {
+            reason: root_cause(&e),
+        }
+    }
+}
+
+/// The functions behind Postgres' `ereport` macro, which pgrx leaves out of its bindings.
+mod ereport {
+    use core::ffi::{c_char, c_int};
+
+    use pgrx::pg_sys;
+
+    /// The source location reported with every error.
+    const FILENAME: *const c_char = concat!(file!(), "\0").as_ptr().cast();
+    const FUNCNAME: *const c_char = c"Error::report".as_ptr();
+
+    extern "C" {
+        pub fn errcode(sqlerrcode: c_int) -> c_int;
+        pub fn errmsg(fmt: *const c_char, ...) -> c_int;
+        pub fn errdetail(fmt: *const c_char, ...) -> c_int;
+        pub fn errhint(fmt: *const c_char, ...) -> c_int;
+        pub fn errhidestmt(hide_stmt: bool) -> c_int;
+        pub fn errhidecontext(hide_ctx: bool) -> c_int;
+    }
+
+    #[cfg(not(any(feature = "pg11", feature = "pg12")))]
+    extern "C" {
+        fn errstart(elevel: c_int, domain: *const c_char) -> bool;
+        fn errfinish(filename: *const c_char, lineno: c_int, funcname: *const c_char);
+    }
+
+    #[cfg(any(feature = "pg11", feature = "pg12"))]
+    extern "C" {
+        fn errstart(
+            elevel: c_int,
+            filename: *const c_char,
+            lineno: c_int,
+            funcname: *const c_char,
+            domain: *const c_char,
+        ) -> bool;
+        fn errfinish(dummy: c_int, ...);
+    }
+
+    /// Starts an `ERROR` report, which Postgres always emits.
+    pub unsafe fn start() {
+        #[cfg(not(any(feature = "pg11", feature = "pg12")))]
+        errstart(pg_sys::ERROR as c_int, core::ptr::null());
+        #[cfg(any(feature = "pg11", feature = "pg12"))]
+        errstart(
+            pg_sys::ERROR as c_int,
+            FILENAME,
+            line!() as c_int,
+            FUNCNAME,
+            core::ptr::null(),
+        );
+    }
+
+    /// Raises the report, longjmp-ing to the innermost Postgres exception handler.
+    pub unsafe fn finish() {
+        #[cfg(not(any(feature = "pg11", feature = "pg12")))]
+        errfinish(FILENAME, line!() as c_int, FUNCNAME);
+        #[cfg(any(feature = "pg11", feature = "pg12"))]
+        errfinish(0);
+    }
+}
+
+/// Encodes a SQLSTATE the way Postgres' `MAKE_SQLSTATE` macro does.
+const fn make_sqlstate(code: [u8; 5]) -> c_int {
+    let mut sqlstate = 0;
+    let mut i = 0;
+    while i < code.len() {
+        sqlstate |= ((code[i].wrapping_sub(b'0') & 0x3f) as c_int) << (6 * i);
+        i += 1;
+    }
+    sqlstate
+}
+
+/// Errors only carry text from Postgres and the extension, which never contains a NUL byte.
+fn to_cstring(s: String) -> CString {
+    CString::new(s).expect("error text contains a NUL byte")
+}
+
+/// Parses the `Hrp` (Human Readable Part) passed to an encoder.
+pub fn parse_hrp(hrp: &str) -> Result<bech32::Hrp, Error> {
+    bech32::Hrp::parse(hrp).map_err(|error| Error::Hrp {
+        hrp: hrp.to_string(),
+        error,
+    })
+}
+
+/// Collects the elements of the array passed as `argument`, which must not contain `NULL`s.
+pub fn non_null<T: FromDatum>(argument: &str, array: Array<T>) -> Result<Vec<T>, Error> {
+    array
+        .iter()
+        .map(|element| {
+            element.ok_or_else(|| Error::NullElement {
+                argument: argument.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Describes the innermost source of `error`, the outer layers only name the failed stage.
+fn root_cause(error: &(dyn std::error::Error + 'static)) -> String {
+    let mut cause = error;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+/// Converts a byte index into `input` to a 1-based character position.
+fn char_position(input: &str, index: usize) -> usize {
+    input[..index].chars().count() + 1
+}
+
+/// Returns the 1-based position of the first character whose case differs from the first cased
+/// character of `input`.
+fn mixed_case_position(input: &str) -> Option<usize> {
+    let first_upper = input
+        .chars()
+        .find(|c| c.is_ascii_alphabetic())?
+        .is_ascii_uppercase();
+    input
+        .chars()
+        .position(|c| c.is_ascii_alphabetic() && c.is_ascii_uppercase() != first_upper)
+        .map(|i| i + 1)
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    #[pg_test]
+    fn test_invalid_char_position() {
+        let input = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxkbrv";
+        let err = Error::decode(input, bech32::decode(input).unwrap_err());
+        match err {
+            Error::Malformed { position, .. } => assert_eq!(position, Some(42)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[pg_test]
+    fn test_repeated_invalid_char_position() {
+        let input = "union14qemq0vw6ybgc3u3e0aty2e764u4gs5lnxkbrv";
+        let err = Error::decode(input, bech32::decode(input).unwrap_err());
+        match err {
+            Error::Malformed { position, .. } => assert_eq!(position, Some(17)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[pg_test]
+    fn test_invalid_utf8_position() {
+        let input = CStr::from_bytes_with_nul(b"union1\xe9qq\0").unwrap();
+        let err = Error::utf8(input, input.to_str().unwrap_err());
+        assert_eq!(err.sqlstate(), make_sqlstate(*b"22P02"));
+        match err {
+            Error::Malformed { position, .. } => assert_eq!(position, Some(7)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[pg_test]
+    fn test_mixed_case_position() {
+        let input = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnXk4rv";
+        let err = Error::decode(input, bech32::decode(input).unwrap_err());
+        match err {
+            Error::Malformed { position, .. } => assert_eq!(position, Some(40)),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[pg_test]
+    fn test_checksum_error() {
+        let input = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw";
+        let err = Error::decode(input, bech32::decode(input).unwrap_err());
+        assert_eq!(err.sqlstate(), make_sqlstate(*b"22Z01"));
+    }
+
+    #[pg_test]
+    fn test_make_sqlstate() {
+        assert_eq!(
+            make_sqlstate(*b"22P02"),
+            PgSqlErrorCode::ERRCODE_INVALID_TEXT_REPRESENTATION as c_int
+        );
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "unknown bech32 mode")]
+    fn test_unknown_mode() {
+        Spi::run("SELECT bech32_encode('union', '\\x00'::bytea, 'bech33')").unwrap();
+    }
+}
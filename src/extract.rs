@@ -0,0 +1,151 @@
+use pgrx::prelude::*;
+
+use crate::error::non_null;
+use crate::types::checked;
+
+/// The shortest possible bech32 string: a 1 character `Hrp`, the separator and a checksum.
+const MIN_LEN: usize = 8;
+
+/// Find the checksummed bech32 strings in free text, such as memos or support tickets.
+///
+/// Candidates are runs of ASCII letters and digits, and only those with a valid bech32 or bech32m
+/// checksum are returned. `position` is the 1-based character position of the string in `input`.
+/// When `hrp_filter` is given, only strings with one of those `Hrp`s (Human Readable Parts) are
+/// returned, compared case-insensitively. A `NULL` input yields no rows, and `hrp_filter` must not
+/// contain `NULL`s.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_extract(
+    input: Option<&str>,
+    hrp_filter: default!(Option<Array<String>>, "NULL"),
+) -> TableIterator<
+    'static,
+    (
+        name!(position, i32),
+        name!(address, String),
+        name!(hrp, String),
+        name!(data, Vec<u8>),
+        name!(variant, String),
+    ),
+> {
+    let hrp_filter = hrp_filter
+        .map(|filter| non_null("hrp_filter", filter))
+        .transpose()
+        .unwrap_or_else(|e| e.report());
+    let mut rows = Vec::new();
+
+    for (position, candidate) in candidates(input.unwrap_or_default()) {
+        let Ok((parsed, variant)) = checked(candidate, None) else {
+            continue;
+        };
+        let hrp = parsed.hrp();
+        if let Some(filter) = &hrp_filter {
+            if !filter.iter().any(|f| hrp.as_str().eq_ignore_ascii_case(f)) {
+                continue;
+            }
+        }
+
+        rows.push((
+            position as i32,
+            candidate.to_string(),
+            hrp.as_str().to_string(),
+            parsed.byte_iter().collect(),
+            variant.as_str().to_string(),
+        ));
+    }
+
+    TableIterator::new(rows)
+}
+
+/// Splits `input` into runs of ASCII letters and digits long enough to be a bech32 string, with
+/// the 1-based character position of each run.
+fn candidates(input: &str) -> Vec<(usize, &str)> {
+    let mut candidates = Vec::new();
+    let mut start = None;
+
+    let mut chars = input.char_indices().enumerate().peekable();
+    while let Some((position, (index, c))) = chars.next() {
+        if c.is_ascii_alphanumeric() {
+            start.get_or_insert((position + 1, index));
+        }
+        let ends = !c.is_ascii_alphanumeric() || chars.peek().is_none();
+        if let (true, Some((position, begin))) = (ends, start) {
+            let end = if c.is_ascii_alphanumeric() {
+                input.len()
+            } else {
+                index
+            };
+            if end - begin >= MIN_LEN {
+                candidates.push((position, &input[begin..end]));
+            }
+            start = None;
+        }
+    }
+
+    candidates
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const UNION: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+    const COSMOS: &str = "cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada";
+
+    #[pg_test]
+    fn test_candidates() {
+        assert_eq!(
+            candidates("é to union1abcdefg, (x) cosmos1abcdefgh"),
+            vec![(6, "union1abcdefg"), (25, "cosmos1abcdefgh")]
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_extract() {
+        let text = format!(
+            "Please send to {}. Old address: {}, not union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw",
+            UNION, COSMOS
+        );
+        let rows: Vec<_> = bech32_extract(Some(&text), None).collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 16);
+        assert_eq!(rows[0].1, UNION);
+        assert_eq!(rows[1].1, COSMOS);
+        assert_eq!(rows[1].2, "cosmos");
+        assert_eq!(rows[1].4, "bech32");
+    }
+
+    #[pg_test]
+    fn test_bech32_extract_hrp_filter() {
+        let result = Spi::get_one::<String>(&format!(
+            "SELECT string_agg(address, ' ') FROM bech32_extract('{} {}', ARRAY['COSMOS'])",
+            UNION, COSMOS
+        ));
+        assert_eq!(result, Ok(Some(COSMOS.to_string())));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "array argument \"hrp_filter\" must not contain nulls")]
+    fn test_bech32_extract_hrp_filter_null_element() {
+        Spi::run(&format!(
+            "SELECT * FROM bech32_extract('{}', ARRAY['union', NULL])",
+            UNION
+        ))
+        .unwrap();
+    }
+
+    #[pg_test]
+    fn test_bech32_extract_sql() {
+        let result = Spi::get_one::<i32>(&format!(
+            "SELECT position FROM bech32_extract('memo: {}', ARRAY['union'])",
+            UNION
+        ));
+        assert_eq!(result, Ok(Some(7)));
+    }
+
+    #[pg_test]
+    fn test_bech32_extract_null() {
+        let result = Spi::get_one::<i64>("SELECT count(*) FROM bech32_extract(NULL)");
+        assert_eq!(result, Ok(Some(0)));
+    }
+}
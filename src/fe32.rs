@@ -0,0 +1,118 @@
+use bech32::{Checksum, Fe32, Fe32IterExt, Hrp};
+use pgrx::prelude::*;
+
+use crate::error::{non_null, parse_hrp};
+use crate::types::checked;
+use crate::{bech32_variant, Error, Variant};
+
+extension_sql!(
+    "\
+CREATE TYPE Bech32DecodedFe32 AS (
+    hrp text,
+    fe smallint[],
+    variant text
+);",
+    name = "create_bech32_decoded_fe32_type",
+);
+
+const FE32_COMPOSITE_TYPE: &str = "Bech32DecodedFe32";
+
+/// Decode a string with a bech32 or bech32m checksum into the `Hrp`, the data part as 5-bit field
+/// elements `fe` and the detected `variant`, without regrouping the data into bytes.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_decode_fe32(input: &str) -> pgrx::composite_type!('static, FE32_COMPOSITE_TYPE) {
+    let (checked, variant) = checked(input, None).unwrap_or_else(|e| e.report());
+    let fes: Vec<i16> = checked
+        .data_part_ascii_no_checksum()
+        .iter()
+        .map(|&c| {
+            let fe = Fe32::from_char(char::from(c)).expect("data part was validated");
+            i16::from(fe.to_u8())
+        })
+        .collect();
+
+    let mut bech = PgHeapTuple::new_composite_type(FE32_COMPOSITE_TYPE)
+        .unwrap_or_else(|_| panic!("error creating {} composite type", FE32_COMPOSITE_TYPE));
+    bech.set_by_name("hrp", checked.hrp().as_str())
+        .expect("error setting hrp");
+    bech.set_by_name("fe", fes).expect("error setting fe");
+    bech.set_by_name("variant", variant.as_str())
+        .expect("error setting variant");
+    bech
+}
+
+/// Encode the `Hrp` (Human Readable Part) and 5-bit field elements into a lowercase bech32 encoded
+/// string, using the checksum algorithm of `mode`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_encode_fe32(hrp: &str, fe: Array<i16>, mode: bech32_variant) -> String {
+    non_null("fe", fe)
+        .and_then(|fe| encode(hrp, &fe, mode.into()))
+        .unwrap_or_else(|e| e.report())
+}
+
+fn encode(hrp: &str, fe: &[i16], variant: Variant) -> Result<String, Error> {
+    use bech32::{Bech32, Bech32m, NoChecksum};
+
+    let hrp = parse_hrp(hrp)?;
+    let fes = fe
+        .iter()
+        .map(|&value| Fe32::try_from(value).map_err(|_| Error::Fe32(value)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    match variant {
+        Variant::Bech32 => encode_checksum::<Bech32>(&hrp, fes),
+        Variant::Bech32m => encode_checksum::<Bech32m>(&hrp, fes),
+        Variant::NoChecksum => encode_checksum::<NoChecksum>(&hrp, fes),
+    }
+}
+
+fn encode_checksum<Ck: Checksum>(hrp: &Hrp, fes: Vec<Fe32>) -> Result<String, Error> {
+    let encoded_length = hrp.len() + 1 + fes.len() + Ck::CHECKSUM_LENGTH;
+    if encoded_length > Ck::CODE_LENGTH {
+        return Err(Error::TooLong {
+            encoded_length,
+            code_length: Ck::CODE_LENGTH,
+        });
+    }
+
+    Ok(fes.into_iter().with_checksum::<Ck>(hrp).chars().collect())
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+
+    #[pg_test]
+    fn test_bech32_decode_fe32() {
+        let bech = bech32_decode_fe32(ADDRESS);
+        assert_eq!(bech.get_by_name("hrp").unwrap(), Some("union"));
+        let fe = bech.get_by_name::<Vec<i16>>("fe").unwrap().unwrap();
+        assert_eq!(fe.len(), 32);
+        assert_eq!(fe[..4], [21, 0, 25, 27]);
+    }
+
+    #[pg_test]
+    fn test_bech32_fe32_roundtrip() {
+        let result = Spi::get_one::<&str>(&format!(
+            "SELECT bech32_encode_fe32(hrp, fe, variant::bech32_variant) FROM bech32_decode_fe32('{}')",
+            ADDRESS
+        ));
+        assert_eq!(result, Ok(Some(ADDRESS)));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid bech32 field element: 32")]
+    fn test_bech32_encode_fe32_rejects_large_value() {
+        Spi::run("SELECT bech32_encode_fe32('union', ARRAY[0, 32]::smallint[], 'bech32')").unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "array argument \"fe\" must not contain nulls")]
+    fn test_bech32_encode_fe32_rejects_null_element() {
+        Spi::run("SELECT bech32_encode_fe32('union', ARRAY[0, NULL]::smallint[], 'bech32')")
+            .unwrap();
+    }
+}
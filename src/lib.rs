@@ -1,28 +1,142 @@
 use pgrx::prelude::*;
 
+mod bitcoin;
+mod bits;
+mod bolt11;
+mod bolt12;
+mod correction;
+mod error;
+mod extract;
+mod fe32;
+mod lnurl;
+mod normalize;
+mod nostr;
+mod operators;
+mod qr;
+mod secret;
+mod segwit;
+mod tsearch;
+mod types;
+// `ALTER TYPE ... SET (TYPMOD_IN)` is only available from Postgres 13.
+#[cfg(not(any(feature = "pg11", feature = "pg12")))]
+mod typmod;
+
+pub use error::Error;
+pub use types::{bech32_variant, Bech32, Variant};
+
+use error::parse_hrp;
+use zeroize::Zeroizing;
+
 pgrx::pg_module_magic!();
 
+#[pg_guard]
+pub extern "C" fn _PG_init() {
+    secret::init();
+}
+
 extension_sql!(
     "\
-CREATE TYPE Bech32 AS (
+CREATE TYPE Bech32Decoded AS (
     hrp text,
-    data bytea
+    data bytea,
+    variant text
 );",
-    name = "create_bech32_type",
+    name = "create_bech32_decoded_type",
 );
 
-const BECH_COMPOSITE_TYPE: &str = "Bech32";
+const BECH_COMPOSITE_TYPE: &str = "Bech32Decoded";
 
-/// Decode a string with a bech32 or bech32m checksum into the `Hrp` and `data` components.
+/// Decode a string with a bech32 or bech32m checksum into the `Hrp`, `data` and `variant`
+/// components, where `variant` is the checksum algorithm that was detected.
 #[pg_extern(immutable, parallel_safe)]
 pub fn bech32_decode(input: &str) -> pgrx::composite_type!('static, BECH_COMPOSITE_TYPE) {
-    let (hrp, data) = bech32::decode(input).expect("error decoding bech32");
+    decode(input, None).unwrap_or_else(|e| e.report())
+}
+
+/// Decode a string that must use the checksum algorithm of `mode` into the `Hrp`, `data` and
+/// `variant` components.
+/// Supports 3 modes:
+/// - bech32
+/// - bech32m
+/// - nochecksum
+#[pg_extern(immutable, parallel_safe, name = "bech32_decode")]
+pub fn bech32_decode_strict(
+    input: &str,
+    mode: &str,
+) -> pgrx::composite_type!('static, BECH_COMPOSITE_TYPE) {
+    let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+    decode(input, Some(variant)).unwrap_or_else(|e| e.report())
+}
+
+/// Like `bech32_decode`, but returns `NULL` instead of failing on an invalid string.
+#[pg_extern(immutable, parallel_safe)]
+pub fn try_bech32_decode(
+    input: &str,
+) -> Option<pgrx::composite_type!('static, BECH_COMPOSITE_TYPE)> {
+    decode(input, None).ok()
+}
+
+fn decode(
+    input: &str,
+    variant: Option<Variant>,
+) -> Result<pgrx::composite_type!('static, BECH_COMPOSITE_TYPE), Error> {
+    let (checked, variant) = types::checked(input, variant)?;
+
     let mut bech = PgHeapTuple::new_composite_type(BECH_COMPOSITE_TYPE)
         .unwrap_or_else(|_| panic!("error creating {} composite type", BECH_COMPOSITE_TYPE));
-    bech.set_by_name("hrp", hrp.as_str())
+    bech.set_by_name("hrp", checked.hrp().as_str())
         .expect("error setting hrp");
-    bech.set_by_name("data", data).expect("error setting data");
-    bech
+    // The payload might be a secret key, so it is wiped once copied into the tuple.
+    let data = Zeroizing::new(checked.byte_iter().collect::<Vec<u8>>());
+    bech.set_by_name("data", data.as_slice())
+        .expect("error setting data");
+    bech.set_by_name("variant", variant.as_str())
+        .expect("error setting variant");
+    Ok(bech)
+}
+
+/// Checks whether `input` is a valid bech32 string, without failing on invalid input.
+///
+/// When `hrp` is given, the `Hrp` (Human Readable Part) must match it case-insensitively. When
+/// `mode` is given, the string must use that checksum algorithm, otherwise either a bech32 or a
+/// bech32m checksum is accepted. An unknown `mode` is still reported as an error. A `NULL` input
+/// yields `NULL`, so the check can be used on nullable columns.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_is_valid(
+    input: Option<&str>,
+    hrp: default!(Option<&str>, "NULL"),
+    mode: default!(Option<&str>, "NULL"),
+) -> Option<bool> {
+    let input = input?;
+    let parsed = match mode {
+        Some(mode) => {
+            let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+            Bech32::parse_as(input, variant)
+        }
+        None => Bech32::parse(input),
+    };
+
+    Some(match (parsed, hrp) {
+        (Ok(bech), Some(hrp)) => bech.hrp().eq_ignore_ascii_case(hrp),
+        (Ok(_), None) => true,
+        (Err(_), _) => false,
+    })
+}
+
+/// Re-encode a checksummed bech32 string with a different `Hrp` (Human Readable Part), keeping
+/// the payload. The checksum of `input` is validated first, and the output uses the same checksum
+/// algorithm as `input` unless `mode` is given. A `NULL` input or `new_hrp` yields `NULL`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_convert_hrp(
+    input: Option<&str>,
+    new_hrp: Option<&str>,
+    mode: default!(Option<bech32_variant>, "NULL"),
+) -> Option<String> {
+    let (input, new_hrp) = (input?, new_hrp?);
+    let bech = Bech32::parse(input).unwrap_or_else(|e| e.report());
+    let variant = mode.map(Variant::from).unwrap_or(bech.variant());
+
+    Some(encode(new_hrp, bech.data(), variant).unwrap_or_else(|e| e.report()))
 }
 
 /// Encode the `Hrp` (Human Readable Part) and input into a checksummed bech32 encoded string.
@@ -32,18 +146,16 @@ pub fn bech32_decode(input: &str) -> pgrx::composite_type!('static, BECH_COMPOSI
 /// - nochecksum
 #[pg_extern(immutable, parallel_safe)]
 pub fn bech32_encode(hrp: &str, input: &[u8], mode: &str) -> String {
-    use bech32::{Bech32, Bech32m, Hrp, NoChecksum};
-
-    let hrp = Hrp::parse(hrp).expect("error parsing hrp");
-
-    let result = match mode {
-        "bech32" => bech32::encode::<Bech32>(hrp, input),
-        "bech32m" => bech32::encode::<Bech32m>(hrp, input),
-        "nochecksum" => bech32::encode::<NoChecksum>(hrp, input),
-        _ => unimplemented!("only bech32, bech32m and nochecksum are supported"),
-    };
+    let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+    encode(hrp, input, variant).unwrap_or_else(|e| e.report())
+}
 
-    result.unwrap_or_else(|_| panic!("error bech32 encoding using {}", mode))
+/// Like `bech32_encode`, but returns `NULL` instead of failing when the `Hrp` or input cannot be
+/// encoded. An unknown `mode` is still reported as an error.
+#[pg_extern(immutable, parallel_safe)]
+pub fn try_bech32_encode(hrp: &str, input: &[u8], mode: &str) -> Option<String> {
+    let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+    encode(hrp, input, variant).ok()
 }
 
 /// Encode the `Hrp` (Human Readable Part) and input into a checksummed lowercase bech32 encoded string.
@@ -53,18 +165,97 @@ pub fn bech32_encode(hrp: &str, input: &[u8], mode: &str) -> String {
 /// - nochecksum
 #[pg_extern(immutable, parallel_safe)]
 pub fn bech32_encode_lower(hrp: &str, input: &[u8], mode: &str) -> String {
-    use bech32::{Bech32, Bech32m, Hrp, NoChecksum};
+    let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+    encode_lower(hrp, input, variant).unwrap_or_else(|e| e.report())
+}
 
-    let hrp = Hrp::parse(hrp).expect("error parsing hrp");
+/// Encode the `Hrp` (Human Readable Part) and input into a checksummed uppercase bech32 encoded
+/// string, which QR codes can store in the more compact alphanumeric mode.
+/// Supports 3 modes:
+/// - bech32
+/// - bech32m
+/// - nochecksum
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_encode_upper(hrp: &str, input: &[u8], mode: &str) -> String {
+    let variant = Variant::from_mode(mode).unwrap_or_else(|e| e.report());
+    encode_upper(hrp, input, variant).unwrap_or_else(|e| e.report())
+}
+
+/// Encode the `Hrp` (Human Readable Part) and input into a bech32 encoded string, using the checksum
+/// algorithm of `mode`.
+#[pg_extern(immutable, parallel_safe, name = "bech32_encode")]
+pub fn bech32_encode_variant(hrp: &str, input: &[u8], mode: bech32_variant) -> String {
+    encode(hrp, input, mode.into()).unwrap_or_else(|e| e.report())
+}
+
+/// Encode the `Hrp` (Human Readable Part) and input into a lowercase bech32 encoded string, using
+/// the checksum algorithm of `mode`.
+#[pg_extern(immutable, parallel_safe, name = "bech32_encode_lower")]
+pub fn bech32_encode_lower_variant(hrp: &str, input: &[u8], mode: bech32_variant) -> String {
+    encode_lower(hrp, input, mode.into()).unwrap_or_else(|e| e.report())
+}
+
+/// Encode the `Hrp` (Human Readable Part) and input into an uppercase bech32 encoded string, using
+/// the checksum algorithm of `mode`.
+#[pg_extern(immutable, parallel_safe, name = "bech32_encode_upper")]
+pub fn bech32_encode_upper_variant(hrp: &str, input: &[u8], mode: bech32_variant) -> String {
+    encode_upper(hrp, input, mode.into()).unwrap_or_else(|e| e.report())
+}
+
+/// Validate a checksummed bech32 string and return it in uppercase. Mixed case input is rejected.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_to_upper(input: &str) -> String {
+    types::checked(input, None).unwrap_or_else(|e| e.report());
+    input.to_ascii_uppercase()
+}
+
+/// Validate a checksummed bech32 string and return it in lowercase. Mixed case input is rejected.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_to_lower(input: &str) -> String {
+    types::checked(input, None).unwrap_or_else(|e| e.report());
+    input.to_ascii_lowercase()
+}
+
+fn encode(hrp: &str, input: &[u8], variant: Variant) -> Result<String, Error> {
+    use bech32::{Bech32, Bech32m, NoChecksum};
+
+    let hrp = parse_hrp(hrp)?;
 
-    let result = match mode {
-        "bech32" => bech32::encode_lower::<Bech32>(hrp, input),
-        "bech32m" => bech32::encode_lower::<Bech32m>(hrp, input),
-        "nochecksum" => bech32::encode_lower::<NoChecksum>(hrp, input),
-        _ => unimplemented!("only bech32, bech32m and nochecksum are supported"),
+    let result = match variant {
+        Variant::Bech32 => bech32::encode::<Bech32>(hrp, input),
+        Variant::Bech32m => bech32::encode::<Bech32m>(hrp, input),
+        Variant::NoChecksum => bech32::encode::<NoChecksum>(hrp, input),
     };
 
-    result.unwrap_or_else(|_| panic!("error bech32 encoding using {}", mode))
+    Ok(result?)
+}
+
+fn encode_lower(hrp: &str, input: &[u8], variant: Variant) -> Result<String, Error> {
+    use bech32::{Bech32, Bech32m, NoChecksum};
+
+    let hrp = parse_hrp(hrp)?;
+
+    let result = match variant {
+        Variant::Bech32 => bech32::encode_lower::<Bech32>(hrp, input),
+        Variant::Bech32m => bech32::encode_lower::<Bech32m>(hrp, input),
+        Variant::NoChecksum => bech32::encode_lower::<NoChecksum>(hrp, input),
+    };
+
+    Ok(result?)
+}
+
+fn encode_upper(hrp: &str, input: &[u8], variant: Variant) -> Result<String, Error> {
+    use bech32::{Bech32, Bech32m, NoChecksum};
+
+    let hrp = parse_hrp(hrp)?;
+
+    let result = match variant {
+        Variant::Bech32 => bech32::encode_upper::<Bech32>(hrp, input),
+        Variant::Bech32m => bech32::encode_upper::<Bech32m>(hrp, input),
+        Variant::NoChecksum => bech32::encode_upper::<NoChecksum>(hrp, input),
+    };
+
+    Ok(result?)
 }
 
 #[cfg(any(test, feature = "pg_test"))]
@@ -85,6 +276,28 @@ mod tests {
         );
     }
 
+    #[pg_test]
+    fn test_bech32_decode_variant() {
+        let bech = bech32_decode("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv");
+        assert_eq!(bech.get_by_name("variant").unwrap(), Some("bech32"));
+
+        let bech = bech32_decode("abcd14g08d6qejxtdg4y5r3zarvary0c5xw7knqc5r8");
+        assert_eq!(bech.get_by_name("variant").unwrap(), Some("bech32m"));
+    }
+
+    #[pg_test]
+    fn test_bech32_decode_strict() {
+        let bech =
+            bech32_decode_strict("abcd14g08d6qejxtdg4y5r3zarvary0c5xw7knqc5r8", "nochecksum");
+        assert_eq!(bech.get_by_name("variant").unwrap(), Some("nochecksum"));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bech32_decode_strict_rejects_other_variant() {
+        bech32_decode_strict("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv", "bech32m");
+    }
+
     #[pg_test]
     fn test_bech32_encode() {
         let raw = hex::decode("644a2606654a7c0e70bf343ae6b828d3fe448447").unwrap();
@@ -99,6 +312,136 @@ mod tests {
         assert_eq!(bech, "union106paz7c4udumwm9ld9n9v3rju4nue39z4nt8tg")
     }
 
+    #[pg_test]
+    fn test_try_bech32_decode() {
+        assert!(try_bech32_decode("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw").is_none());
+        let bech = try_bech32_decode("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv").unwrap();
+        assert_eq!(bech.get_by_name("hrp").unwrap(), Some("union"));
+    }
+
+    #[pg_test]
+    fn test_try_bech32_encode() {
+        assert_eq!(try_bech32_encode("", &[0], "bech32"), None);
+        assert_eq!(
+            try_bech32_encode("union", &[0], "bech32"),
+            Some(bech32_encode("union", &[0], "bech32"))
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_is_valid() {
+        let addr = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+        assert_eq!(bech32_is_valid(Some(addr), None, None), Some(true));
+        assert_eq!(bech32_is_valid(Some(addr), Some("UNION"), None), Some(true));
+        assert_eq!(
+            bech32_is_valid(Some(addr), Some("union"), Some("bech32")),
+            Some(true)
+        );
+        assert_eq!(
+            bech32_is_valid(Some(addr), Some("cosmos"), None),
+            Some(false)
+        );
+        assert_eq!(
+            bech32_is_valid(Some(addr), None, Some("bech32m")),
+            Some(false)
+        );
+        assert_eq!(
+            bech32_is_valid(Some("not an address"), None, None),
+            Some(false)
+        );
+        assert_eq!(bech32_is_valid(None, Some("union"), None), None);
+    }
+
+    #[pg_test]
+    fn test_bech32_is_valid_in_check_constraint() {
+        Spi::run("CREATE TABLE accounts (owner text CHECK (bech32_is_valid(owner, 'union')))")
+            .unwrap();
+        Spi::run("INSERT INTO accounts VALUES ('union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv')")
+            .unwrap();
+        Spi::run("INSERT INTO accounts VALUES (NULL)").unwrap();
+        let count =
+            Spi::get_one::<i64>("SELECT count(*) FROM accounts WHERE bech32_is_valid(owner)")
+                .unwrap();
+        assert_eq!(count, Some(1));
+    }
+
+    #[pg_test]
+    fn test_bech32_encode_variant() {
+        let raw = hex::decode("644a2606654a7c0e70bf343ae6b828d3fe448447").unwrap();
+        assert_eq!(
+            bech32_encode_variant("union", &raw, bech32_variant::bech32),
+            bech32_encode("union", &raw, "bech32")
+        );
+        assert_eq!(
+            bech32_encode_lower_variant("UNION", &raw, bech32_variant::bech32m),
+            bech32_encode_lower("UNION", &raw, "bech32m")
+        );
+    }
+
+    #[pg_test]
+    fn test_encode_bech_from_enum() {
+        let result = Spi::get_one::<&str>("SELECT bech32_encode('union'::text, decode('644a2606654a7c0e70bf343ae6b828d3fe448447','hex'), 'bech32'::bech32_variant)").unwrap();
+        assert_eq!(
+            result.unwrap(),
+            "union1v39zvpn9ff7quu9lxsawdwpg60lyfpz8pmhfey"
+        )
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input value for enum bech32_variant")]
+    fn test_encode_bech_from_enum_rejects_typo() {
+        Spi::run("SELECT bech32_encode('union', '\\x00'::bytea, 'bech33'::bech32_variant)")
+            .unwrap();
+    }
+
+    #[pg_test]
+    fn test_bech32_convert_hrp() {
+        let addr = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+        assert_eq!(
+            bech32_convert_hrp(Some(addr), Some("cosmos"), None).as_deref(),
+            Some("cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada")
+        );
+        assert_eq!(
+            bech32_convert_hrp(Some(addr), Some("osmo"), Some(bech32_variant::bech32m)).as_deref(),
+            Some("osmo14qemq0vw6y3gc3u3e0aty2e764u4gs5lyk5p7d")
+        );
+        assert_eq!(bech32_convert_hrp(None, Some("cosmos"), None), None);
+        assert_eq!(bech32_convert_hrp(Some(addr), None, None), None);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bech32_convert_hrp_rejects_bad_checksum() {
+        bech32_convert_hrp(
+            Some("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw"),
+            Some("cosmos"),
+            None,
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_encode_upper() {
+        let data = hex::decode("644a2606654a7c0e70bf343ae6b828d3fe448447").unwrap();
+        assert_eq!(
+            bech32_encode_upper("union", &data, "bech32"),
+            "UNION1V39ZVPN9FF7QUU9LXSAWDWPG60LYFPZ8PMHFEY"
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_to_upper_and_lower() {
+        let addr = "union1v39zvpn9ff7quu9lxsawdwpg60lyfpz8pmhfey";
+        let upper = bech32_to_upper(addr);
+        assert_eq!(upper, "UNION1V39ZVPN9FF7QUU9LXSAWDWPG60LYFPZ8PMHFEY");
+        assert_eq!(bech32_to_lower(&upper), addr);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_to_upper_rejects_mixed_case() {
+        bech32_to_upper("union1v39zvpn9ff7quu9lxsawdwpg60lyfpz8pmhfEY");
+    }
+
     #[pg_test]
     fn test_encode_bech_from_hex() {
         let result = Spi::get_one::<&str>("SELECT bech32_encode('union'::text, decode('644a2606654a7c0e70bf343ae6b828d3fe448447','hex'), 'bech32'::text)").unwrap();
@@ -0,0 +1,94 @@
+use bech32::primitives::decode::UncheckedHrpstring;
+use bech32::Hrp;
+use pgrx::prelude::*;
+
+use crate::types::UnboundedBech32;
+use crate::{Error, Variant};
+
+const HRP: Hrp = Hrp::parse_unchecked("lnurl");
+
+/// The URI scheme LNURLs are often prefixed with in links and QR codes.
+const SCHEME: &str = "lightning:";
+
+/// Decode an LNURL into the URL it wraps.
+///
+/// The checksum is validated without the 1023 character limit, and a `lightning:` prefix is
+/// accepted.
+#[pg_extern(immutable, parallel_safe)]
+pub fn lnurl_decode(input: &str) -> String {
+    decode(input).unwrap_or_else(|e| e.report())
+}
+
+/// Encode a URL as an LNURL, in the uppercase form that QR codes store most compactly.
+#[pg_extern(immutable, parallel_safe)]
+pub fn lnurl_encode(url: &str) -> String {
+    encode(url).unwrap_or_else(|e| e.report())
+}
+
+fn decode(input: &str) -> Result<String, Error> {
+    let lnurl = match input.get(..SCHEME.len()) {
+        Some(scheme) if scheme.eq_ignore_ascii_case(SCHEME) => &input[SCHEME.len()..],
+        _ => input,
+    };
+
+    let unchecked = UncheckedHrpstring::new(lnurl).map_err(|e| Error::parse(lnurl, e))?;
+    let checked = unchecked
+        .validate_and_remove_checksum::<UnboundedBech32>()
+        .map_err(|e| Error::checksum(lnurl, e, Some(Variant::Bech32)))?;
+    if checked.hrp() != HRP {
+        return Err(Error::UnexpectedHrp {
+            input: lnurl.to_string(),
+            expected: HRP.to_string(),
+            found: checked.hrp().to_lowercase(),
+        });
+    }
+
+    String::from_utf8(checked.byte_iter().collect()).map_err(|_| Error::Lnurl {
+        reason: "the URL is not valid UTF-8".to_string(),
+    })
+}
+
+fn encode(url: &str) -> Result<String, Error> {
+    if url.is_empty() {
+        return Err(Error::Lnurl {
+            reason: "the URL is empty".to_string(),
+        });
+    }
+    Ok(bech32::encode_upper::<UnboundedBech32>(
+        HRP,
+        url.as_bytes(),
+    )?)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    /// The example from LUD-01.
+    const LNURL: &str = "LNURL1DP68GURN8GHJ7UM9WFMXJCM99E3K7MF0V9CXJ0M385EKVCENXC6R2C35XVUKXEFCV5MKVV34X5EKZD3EV56NYD3HXQURZEPEXEJXXEPNXSCRVWFNV9NXZCN9XQ6XYEFHVGCXXCMYXYMNSERXFQ5FNS";
+    const URL: &str =
+        "https://service.com/api?q=3fc3645b439ce8e7f2553a69e5267081d96dcd340693afabe04be7b0ccd178df";
+
+    #[pg_test]
+    fn test_lnurl() {
+        assert_eq!(lnurl_encode(URL), LNURL);
+        assert_eq!(lnurl_decode(LNURL), URL);
+        assert_eq!(lnurl_decode(&format!("lightning:{}", LNURL)), URL);
+        assert_eq!(lnurl_decode(&LNURL.to_lowercase()), URL);
+    }
+
+    #[pg_test]
+    fn test_lnurl_longer_than_code_length() {
+        let url = format!("https://service.com/api?q={}", "a".repeat(700));
+        let lnurl = lnurl_encode(&url);
+        assert!(lnurl.len() > 1023);
+        assert_eq!(lnurl_decode(&lnurl), url);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "does not have the human-readable part \"lnurl\"")]
+    fn test_lnurl_decode_rejects_other_hrp() {
+        lnurl_decode("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv");
+    }
+}
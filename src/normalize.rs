@@ -0,0 +1,128 @@
+use pgrx::prelude::*;
+use pgrx::TryFromDatumError;
+
+use crate::types::checked;
+use crate::Error;
+
+/// Normalize a user submitted bech32 string into its canonical lowercase form.
+///
+/// Surrounding whitespace is trimmed, then the checksum is validated and mixed case input is
+/// rejected. When `expected_hrp` is given, the `Hrp` (Human Readable Part) must match it
+/// case-insensitively. A `NULL` input yields `NULL`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_normalize(
+    input: Option<&str>,
+    expected_hrp: default!(Option<&str>, "NULL"),
+) -> Option<String> {
+    Some(normalize(input?, expected_hrp).unwrap_or_else(|e| e.report()))
+}
+
+fn normalize(input: &str, expected_hrp: Option<&str>) -> Result<String, Error> {
+    let input = input.trim();
+    let (checked, _) = checked(input, None)?;
+
+    if let Some(expected) = expected_hrp {
+        if !checked.hrp().as_str().eq_ignore_ascii_case(expected) {
+            return Err(Error::UnexpectedHrp {
+                input: input.to_string(),
+                expected: expected.to_string(),
+                found: checked.hrp().to_lowercase(),
+            });
+        }
+    }
+
+    Ok(input.to_ascii_lowercase())
+}
+
+/// Trigger that normalizes the `text` columns named in its arguments with `bech32_normalize`.
+///
+/// ```sql
+/// CREATE TRIGGER normalize_addresses
+///     BEFORE INSERT OR UPDATE ON accounts
+///     FOR EACH ROW EXECUTE FUNCTION bech32_normalize_trigger('owner', 'recipient');
+/// ```
+#[pg_trigger]
+pub fn bech32_normalize_trigger<'a>(
+    trigger: &'a PgTrigger<'a>,
+) -> Result<Option<PgHeapTuple<'a, impl WhoAllocated>>, TryFromDatumError> {
+    let mut row = trigger
+        .new()
+        .expect("bech32_normalize_trigger must be fired BEFORE INSERT OR UPDATE FOR EACH ROW")
+        .into_owned();
+    let columns = trigger
+        .extra_args()
+        .expect("error reading bech32_normalize_trigger arguments");
+
+    for column in columns {
+        if let Some(value) = row.get_by_name::<String>(&column)? {
+            let normalized = normalize(&value, None).unwrap_or_else(|e| e.report());
+            row.set_by_name(&column, normalized)?;
+        }
+    }
+
+    Ok(Some(row))
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+
+    #[pg_test]
+    fn test_bech32_normalize() {
+        assert_eq!(
+            bech32_normalize(
+                Some(" UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV\n"),
+                None
+            )
+            .as_deref(),
+            Some(ADDRESS)
+        );
+        assert_eq!(
+            bech32_normalize(Some(ADDRESS), Some("UNION")).as_deref(),
+            Some(ADDRESS)
+        );
+        assert_eq!(bech32_normalize(None, Some("union")), None);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "does not have the human-readable part \"cosmos\"")]
+    fn test_bech32_normalize_rejects_other_hrp() {
+        bech32_normalize(Some(ADDRESS), Some("cosmos"));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid input syntax for type bech32")]
+    fn test_bech32_normalize_rejects_mixed_case() {
+        bech32_normalize(Some("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnXk4rv"), None);
+    }
+
+    #[pg_test]
+    fn test_bech32_normalize_trigger() {
+        Spi::run(
+            "CREATE TABLE accounts (owner text, memo text);
+             CREATE TRIGGER normalize_owner BEFORE INSERT OR UPDATE ON accounts
+                 FOR EACH ROW EXECUTE FUNCTION bech32_normalize_trigger('owner');
+             INSERT INTO accounts VALUES ('  UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV ', ' memo ');",
+        )
+        .unwrap();
+        let owner = Spi::get_one::<String>("SELECT owner FROM accounts").unwrap();
+        assert_eq!(owner.as_deref(), Some(ADDRESS));
+        let memo = Spi::get_one::<String>("SELECT memo FROM accounts").unwrap();
+        assert_eq!(memo.as_deref(), Some(" memo "));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bech32_normalize_trigger_rejects_bad_checksum() {
+        Spi::run(
+            "CREATE TABLE accounts (owner text);
+             CREATE TRIGGER normalize_owner BEFORE INSERT ON accounts
+                 FOR EACH ROW EXECUTE FUNCTION bech32_normalize_trigger('owner');
+             INSERT INTO accounts VALUES ('union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw');",
+        )
+        .unwrap();
+    }
+}
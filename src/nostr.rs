@@ -0,0 +1,427 @@
+use bech32::{Bech32, Hrp};
+use pgrx::prelude::*;
+use pgrx::JsonB;
+use serde_json::{json, Value};
+use zeroize::Zeroizing;
+
+use crate::error::non_null;
+use crate::types::checked;
+use crate::{Error, Variant};
+
+/// The TLV types of `nprofile`, `nevent` and `naddr` entities. The meaning of `SPECIAL` depends on
+/// the entity.
+const TLV_SPECIAL: u8 = 0;
+const TLV_RELAY: u8 = 1;
+const TLV_AUTHOR: u8 = 2;
+const TLV_KIND: u8 = 3;
+
+/// The length of public keys, secret keys and event ids.
+const KEY_LENGTH: usize = 32;
+
+/// Decode a NIP-19 entity into a `jsonb` object with its `type` and fields.
+///
+/// `npub`, `nsec` and `note` decode into a hex `pubkey`, `seckey` or `id`. The TLV payloads of
+/// `nprofile`, `nevent` and `naddr` decode into the `pubkey`, `id`, `author`, `identifier`, `kind`
+/// and `relays` they carry. Unknown TLV entries are ignored.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_decode(input: &str) -> JsonB {
+    decode(input).map(JsonB).unwrap_or_else(|e| e.report())
+}
+
+/// Encode a 32 byte public key as an `npub` entity.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_npub(pubkey: &[u8]) -> String {
+    encode_key("npub", pubkey, "pubkey").unwrap_or_else(|e| e.report())
+}
+
+/// Encode a 32 byte secret key as an `nsec` entity.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_nsec(seckey: &[u8]) -> String {
+    encode_key("nsec", seckey, "seckey").unwrap_or_else(|e| e.report())
+}
+
+/// Encode a 32 byte event id as a `note` entity.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_note(id: &[u8]) -> String {
+    encode_key("note", id, "id").unwrap_or_else(|e| e.report())
+}
+
+/// Encode a public key and the relays it can be found on as an `nprofile` entity. A `NULL`
+/// `pubkey` yields `NULL`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_nprofile(
+    pubkey: Option<&[u8]>,
+    relays: default!(Option<Array<String>>, "NULL"),
+) -> Option<String> {
+    let pubkey = pubkey?;
+    Some(
+        collect_relays(relays)
+            .and_then(|relays| encode_nprofile(pubkey, relays.as_deref()))
+            .unwrap_or_else(|e| e.report()),
+    )
+}
+
+/// Encode an event id as an `nevent` entity, with optional relays, author and kind hints. A `NULL`
+/// `id` yields `NULL`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_nevent(
+    id: Option<&[u8]>,
+    relays: default!(Option<Array<String>>, "NULL"),
+    author: default!(Option<&[u8]>, "NULL"),
+    kind: default!(Option<i64>, "NULL"),
+) -> Option<String> {
+    let id = id?;
+    Some(
+        collect_relays(relays)
+            .and_then(|relays| encode_nevent(id, relays.as_deref(), author, kind))
+            .unwrap_or_else(|e| e.report()),
+    )
+}
+
+/// Encode the coordinate of a replaceable event, its `d` tag `identifier`, author and kind, as an
+/// `naddr` entity. A `NULL` `identifier`, `pubkey` or `kind` yields `NULL`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn nostr_naddr(
+    identifier: Option<&str>,
+    pubkey: Option<&[u8]>,
+    kind: Option<i64>,
+    relays: default!(Option<Array<String>>, "NULL"),
+) -> Option<String> {
+    let (identifier, pubkey, kind) = (identifier?, pubkey?, kind?);
+    Some(
+        collect_relays(relays)
+            .and_then(|relays| encode_naddr(identifier, pubkey, kind, relays.as_deref()))
+            .unwrap_or_else(|e| e.report()),
+    )
+}
+
+/// Collects the `relays` passed to an encoder, which must not contain `NULL`s.
+fn collect_relays(relays: Option<Array<String>>) -> Result<Option<Vec<String>>, Error> {
+    relays.map(|relays| non_null("relays", relays)).transpose()
+}
+
+fn decode(input: &str) -> Result<Value, Error> {
+    let (checked, _) = checked(input, Some(Variant::Bech32))?;
+    // An `nsec` holds a secret key, so the raw payload is wiped. The hex copy in the result is not.
+    let data = Zeroizing::new(checked.byte_iter().collect::<Vec<u8>>());
+    let prefix = checked.hrp().to_lowercase();
+
+    match prefix.as_str() {
+        "npub" => Ok(json!({ "type": "npub", "pubkey": hex_key(&data, "pubkey")? })),
+        "nsec" => Ok(json!({ "type": "nsec", "seckey": hex_key(&data, "seckey")? })),
+        "note" => Ok(json!({ "type": "note", "id": hex_key(&data, "id")? })),
+        "nprofile" => {
+            let tlv = Tlv::parse(&data)?;
+            let pubkey = tlv.key(TLV_SPECIAL, "pubkey")?;
+            Ok(json!({
+                "type": "nprofile",
+                "pubkey": pubkey.ok_or_else(|| missing("nprofile", "pubkey"))?,
+                "relays": tlv.relays()?,
+            }))
+        }
+        "nevent" => {
+            let tlv = Tlv::parse(&data)?;
+            let id = tlv.key(TLV_SPECIAL, "id")?;
+            Ok(json!({
+                "type": "nevent",
+                "id": id.ok_or_else(|| missing("nevent", "id"))?,
+                "relays": tlv.relays()?,
+                "author": tlv.key(TLV_AUTHOR, "author")?,
+                "kind": tlv.kind()?,
+            }))
+        }
+        "naddr" => {
+            let tlv = Tlv::parse(&data)?;
+            let (pubkey, kind) = (tlv.key(TLV_AUTHOR, "pubkey")?, tlv.kind()?);
+            Ok(json!({
+                "type": "naddr",
+                "identifier": tlv.identifier()?,
+                "pubkey": pubkey.ok_or_else(|| missing("naddr", "pubkey"))?,
+                "kind": kind.ok_or_else(|| missing("naddr", "kind"))?,
+                "relays": tlv.relays()?,
+            }))
+        }
+        _ => Err(Error::Nostr {
+            reason: format!("unknown prefix \"{}\"", prefix),
+        }),
+    }
+}
+
+/// The entries of a TLV payload, in order.
+struct Tlv<'a> {
+    entries: Vec<(u8, &'a [u8])>,
+}
+
+impl<'a> Tlv<'a> {
+    fn parse(mut data: &'a [u8]) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        while let [kind, len, rest @ ..] = data {
+            let len = usize::from(*len);
+            if rest.len() < len {
+                break;
+            }
+            entries.push((*kind, &rest[..len]));
+            data = &rest[len..];
+        }
+
+        if !data.is_empty() {
+            return Err(Error::Nostr {
+                reason: "the TLV payload is truncated".to_string(),
+            });
+        }
+        Ok(Tlv { entries })
+    }
+
+    /// Returns the values of the entries of type `kind`.
+    fn values(&self, kind: u8) -> impl Iterator<Item = &'a [u8]> + '_ {
+        self.entries
+            .iter()
+            .filter(move |(k, _)| *k == kind)
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the first key of type `kind` in hex.
+    fn key(&self, kind: u8, name: &str) -> Result<Option<String>, Error> {
+        self.values(kind)
+            .next()
+            .map(|value| hex_key(value, name))
+            .transpose()
+    }
+
+    fn relays(&self) -> Result<Vec<String>, Error> {
+        self.values(TLV_RELAY)
+            .map(|value| utf8(value, "relay"))
+            .collect()
+    }
+
+    fn kind(&self) -> Result<Option<u32>, Error> {
+        self.values(TLV_KIND)
+            .next()
+            .map(|value| {
+                <[u8; 4]>::try_from(value)
+                    .map(u32::from_be_bytes)
+                    .map_err(|_| Error::Nostr {
+                        reason: format!("kind must be 4 bytes, not {}", value.len()),
+                    })
+            })
+            .transpose()
+    }
+
+    /// Returns the `d` tag of an `naddr`, which may be empty but must be present.
+    fn identifier(&self) -> Result<String, Error> {
+        let value = self
+            .values(TLV_SPECIAL)
+            .next()
+            .ok_or_else(|| missing("naddr", "identifier"))?;
+        utf8(value, "identifier")
+    }
+}
+
+fn hex_key(value: &[u8], name: &str) -> Result<String, Error> {
+    check_key(value, name)?;
+    Ok(hex::encode(value))
+}
+
+fn check_key(value: &[u8], name: &str) -> Result<(), Error> {
+    match value.len() {
+        KEY_LENGTH => Ok(()),
+        len => Err(Error::Nostr {
+            reason: format!("{} must be {} bytes, not {}", name, KEY_LENGTH, len),
+        }),
+    }
+}
+
+fn utf8(value: &[u8], name: &str) -> Result<String, Error> {
+    String::from_utf8(value.to_vec()).map_err(|_| Error::Nostr {
+        reason: format!("{} is not valid UTF-8", name),
+    })
+}
+
+fn missing(entity: &str, name: &str) -> Error {
+    Error::Nostr {
+        reason: format!("{} is missing the {}", entity, name),
+    }
+}
+
+fn encode_key(prefix: &str, key: &[u8], name: &str) -> Result<String, Error> {
+    check_key(key, name)?;
+    encode(prefix, key)
+}
+
+fn encode_nprofile(pubkey: &[u8], relays: Option<&[String]>) -> Result<String, Error> {
+    let mut tlv = Vec::new();
+    push_key(&mut tlv, TLV_SPECIAL, pubkey, "pubkey")?;
+    push_relays(&mut tlv, relays)?;
+    encode("nprofile", &tlv)
+}
+
+fn encode_nevent(
+    id: &[u8],
+    relays: Option<&[String]>,
+    author: Option<&[u8]>,
+    kind: Option<i64>,
+) -> Result<String, Error> {
+    let mut tlv = Vec::new();
+    push_key(&mut tlv, TLV_SPECIAL, id, "id")?;
+    push_relays(&mut tlv, relays)?;
+    if let Some(author) = author {
+        push_key(&mut tlv, TLV_AUTHOR, author, "author")?;
+    }
+    if let Some(kind) = kind {
+        push_kind(&mut tlv, kind)?;
+    }
+    encode("nevent", &tlv)
+}
+
+fn encode_naddr(
+    identifier: &str,
+    pubkey: &[u8],
+    kind: i64,
+    relays: Option<&[String]>,
+) -> Result<String, Error> {
+    let mut tlv = Vec::new();
+    push(&mut tlv, TLV_SPECIAL, identifier.as_bytes(), "identifier")?;
+    push_relays(&mut tlv, relays)?;
+    push_key(&mut tlv, TLV_AUTHOR, pubkey, "pubkey")?;
+    push_kind(&mut tlv, kind)?;
+    encode("naddr", &tlv)
+}
+
+fn encode(prefix: &str, data: &[u8]) -> Result<String, Error> {
+    Ok(bech32::encode_lower::<Bech32>(
+        Hrp::parse_unchecked(prefix),
+        data,
+    )?)
+}
+
+fn push(tlv: &mut Vec<u8>, kind: u8, value: &[u8], name: &str) -> Result<(), Error> {
+    let len = u8::try_from(value.len()).map_err(|_| Error::Nostr {
+        reason: format!("{} is {} bytes, the maximum is 255", name, value.len()),
+    })?;
+    tlv.extend([kind, len]);
+    tlv.extend_from_slice(value);
+    Ok(())
+}
+
+fn push_key(tlv: &mut Vec<u8>, kind: u8, key: &[u8], name: &str) -> Result<(), Error> {
+    check_key(key, name)?;
+    push(tlv, kind, key, name)
+}
+
+fn push_relays(tlv: &mut Vec<u8>, relays: Option<&[String]>) -> Result<(), Error> {
+    relays
+        .unwrap_or_default()
+        .iter()
+        .try_for_each(|relay| push(tlv, TLV_RELAY, relay.as_bytes(), "relay"))
+}
+
+fn push_kind(tlv: &mut Vec<u8>, kind: i64) -> Result<(), Error> {
+    let kind = u32::try_from(kind).map_err(|_| Error::Nostr {
+        reason: format!("kind must be between 0 and {}, not {}", u32::MAX, kind),
+    })?;
+    push(tlv, TLV_KIND, &kind.to_be_bytes(), "kind")
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const PUBKEY: &str = "7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e";
+    const NPUB: &str = "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg";
+    const NPROFILE: &str = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+    const EVENT_ID: &str = "b9f5441e45ca39179320e0031cfb18e34078673dcc3d3e3a3b3a981760aa5696";
+
+    #[pg_test]
+    fn test_nostr_npub() {
+        assert_eq!(nostr_npub(&hex::decode(PUBKEY).unwrap()), NPUB);
+        assert_eq!(
+            nostr_decode(NPUB).0,
+            json!({ "type": "npub", "pubkey": PUBKEY })
+        );
+    }
+
+    #[pg_test]
+    fn test_nostr_nprofile() {
+        // The example from NIP-19.
+        let relays = vec![
+            "wss://r.x.com".to_string(),
+            "wss://djbas.sadkb.com".to_string(),
+        ];
+        let pubkey = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+        let result = Spi::get_one::<String>(&format!(
+            "SELECT nostr_nprofile('\\x{}', ARRAY['{}'])",
+            pubkey,
+            relays.join("', '")
+        ));
+        assert_eq!(result, Ok(Some(NPROFILE.to_string())));
+        assert_eq!(nostr_nprofile(None, None), None);
+        assert_eq!(
+            nostr_decode(NPROFILE).0,
+            json!({ "type": "nprofile", "pubkey": pubkey, "relays": relays })
+        );
+    }
+
+    #[pg_test]
+    fn test_nostr_nevent() {
+        let id = hex::decode(EVENT_ID).unwrap();
+        let author = hex::decode(PUBKEY).unwrap();
+        let nevent = nostr_nevent(Some(&id), None, Some(&author), Some(1)).unwrap();
+        assert_eq!(
+            nevent,
+            "nevent1qqstna2yrezu5wghjvswqqculvvwxsrcvu7uc0f78gan4xqhvz49d9szypl8a8zz4ydlauvl4y57tldpkuhqa0q6fsg5zee7y72zxnvx4h05uqcyqqqqqqg7cv0wu"
+        );
+        assert_eq!(
+            nostr_decode(&nevent).0,
+            json!({
+                "type": "nevent",
+                "id": EVENT_ID,
+                "relays": [],
+                "author": PUBKEY,
+                "kind": 1,
+            })
+        );
+    }
+
+    #[pg_test]
+    fn test_nostr_naddr_sql() {
+        let result = Spi::get_one::<String>(&format!(
+            "SELECT nostr_decode(nostr_naddr('hello', '\\x{}', 30023, ARRAY['wss://relay.com']))::text",
+            PUBKEY
+        ));
+        let expected = json!({
+            "type": "naddr",
+            "identifier": "hello",
+            "pubkey": PUBKEY,
+            "kind": 30023,
+            "relays": ["wss://relay.com"],
+        });
+        let decoded: Value = serde_json::from_str(&result.unwrap().unwrap()).unwrap();
+        assert_eq!(decoded, expected);
+    }
+
+    #[pg_test]
+    fn test_nostr_encoders_null() {
+        let result = Spi::get_one::<bool>(&format!(
+            "SELECT nostr_nevent(NULL) IS NULL AND nostr_naddr(NULL, '\\x{}', 30023) IS NULL",
+            PUBKEY
+        ));
+        assert_eq!(result, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "array argument \"relays\" must not contain nulls")]
+    fn test_nostr_nprofile_rejects_null_relay() {
+        Spi::run(&format!(
+            "SELECT nostr_nprofile('\\x{}', ARRAY['wss://relay.com', NULL])",
+            PUBKEY
+        ))
+        .unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid nostr entity")]
+    fn test_nostr_npub_rejects_short_key() {
+        nostr_npub(&[0; 31]);
+    }
+}
@@ -0,0 +1,153 @@
+use core::cmp::Ordering;
+
+use pgrx::prelude::*;
+
+use crate::Bech32;
+
+/// Check whether two `bech32` values carry the same payload, regardless of their `Hrp` (Human
+/// Readable Part) and checksum variant. This finds the same account on different Cosmos SDK chains.
+#[pg_operator(immutable, parallel_safe)]
+#[opname(=~)]
+#[negator(<>~)]
+#[commutator(=~)]
+#[restrict(eqsel)]
+#[join(eqjoinsel)]
+#[hashes]
+#[merges]
+pub fn bech32_same_payload(a: Bech32, b: Bech32) -> bool {
+    a.data() == b.data()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(<>~)]
+#[negator(=~)]
+#[commutator(<>~)]
+#[restrict(neqsel)]
+#[join(neqjoinsel)]
+pub fn bech32_payload_ne(a: Bech32, b: Bech32) -> bool {
+    a.data() != b.data()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(<~)]
+#[negator(>=~)]
+#[commutator(>~)]
+#[restrict(scalarltsel)]
+#[join(scalarltjoinsel)]
+pub fn bech32_payload_lt(a: Bech32, b: Bech32) -> bool {
+    a.data() < b.data()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(<=~)]
+#[negator(>~)]
+#[commutator(>=~)]
+#[restrict(scalarlesel)]
+#[join(scalarlejoinsel)]
+pub fn bech32_payload_le(a: Bech32, b: Bech32) -> bool {
+    a.data() <= b.data()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(>~)]
+#[negator(<=~)]
+#[commutator(<~)]
+#[restrict(scalargtsel)]
+#[join(scalargtjoinsel)]
+pub fn bech32_payload_gt(a: Bech32, b: Bech32) -> bool {
+    a.data() > b.data()
+}
+
+#[pg_operator(immutable, parallel_safe)]
+#[opname(>=~)]
+#[negator(<~)]
+#[commutator(<=~)]
+#[restrict(scalargesel)]
+#[join(scalargejoinsel)]
+pub fn bech32_payload_ge(a: Bech32, b: Bech32) -> bool {
+    a.data() >= b.data()
+}
+
+/// Btree support function of `bech32_payload_ops`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_payload_cmp(a: Bech32, b: Bech32) -> i32 {
+    match a.data().cmp(b.data()) {
+        Ordering::Less => -1,
+        Ordering::Equal => 0,
+        Ordering::Greater => 1,
+    }
+}
+
+/// Hash support function of `bech32_payload_hash_ops`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_payload_hash(a: Bech32) -> i32 {
+    pgrx::misc::pgrx_seahash(&a.data()) as i32
+}
+
+extension_sql!(
+    "\
+CREATE OPERATOR CLASS bech32_payload_ops FOR TYPE Bech32 USING btree AS
+    OPERATOR 1 <~,
+    OPERATOR 2 <=~,
+    OPERATOR 3 =~,
+    OPERATOR 4 >=~,
+    OPERATOR 5 >~,
+    FUNCTION 1 bech32_payload_cmp(Bech32, Bech32);
+
+CREATE OPERATOR CLASS bech32_payload_hash_ops FOR TYPE Bech32 USING hash AS
+    OPERATOR 1 =~,
+    FUNCTION 1 bech32_payload_hash(Bech32);",
+    name = "create_bech32_payload_opclasses",
+    requires = [
+        bech32_same_payload,
+        bech32_payload_ne,
+        bech32_payload_lt,
+        bech32_payload_le,
+        bech32_payload_gt,
+        bech32_payload_ge,
+        bech32_payload_cmp,
+        bech32_payload_hash
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    const UNION: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+    const COSMOS: &str = "cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada";
+    const OTHER: &str = "union1qqqsyqcyq5rqwzqfpg9scrgwpugpzysnfpsjlg";
+
+    #[pg_test]
+    fn test_bech32_same_payload() {
+        let union = Bech32::parse(UNION).unwrap();
+        let cosmos = Bech32::parse(COSMOS).unwrap();
+        let other = Bech32::parse(OTHER).unwrap();
+        assert!(bech32_same_payload(union.clone(), cosmos));
+        assert!(!bech32_same_payload(union, other));
+    }
+
+    #[pg_test]
+    fn test_bech32_same_payload_operator() {
+        let result = Spi::get_one::<bool>(&format!("SELECT '{}'::bech32 =~ '{}'", UNION, COSMOS));
+        assert_eq!(result, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_bech32_payload_ops_join() {
+        Spi::run(&format!(
+            "CREATE TABLE union_accounts (addr bech32);
+             CREATE TABLE cosmos_accounts (addr bech32);
+             CREATE INDEX ON cosmos_accounts (addr bech32_payload_ops);
+             INSERT INTO union_accounts VALUES ('{}'), ('{}');
+             INSERT INTO cosmos_accounts VALUES ('{}');",
+            UNION, OTHER, COSMOS
+        ))
+        .unwrap();
+        let result = Spi::get_one::<i64>(
+            "SELECT count(*) FROM union_accounts u JOIN cosmos_accounts c ON u.addr =~ c.addr",
+        );
+        assert_eq!(result, Ok(Some(1)));
+    }
+}
@@ -0,0 +1,117 @@
+use pgrx::prelude::*;
+use qrcode::render::svg;
+use qrcode::{Color, EcLevel, QrCode};
+
+use crate::types::checked;
+use crate::Error;
+
+/// Width of the light border around the code, in modules, as required by the QR specification.
+const QUIET_ZONE: usize = 4;
+/// Size of a single module in a PNG, in pixels.
+const PNG_MODULE_SIZE: usize = 8;
+
+/// Render a checksummed bech32 string as an SVG QR code.
+///
+/// The string is uppercased first so that it is stored in the compact alphanumeric mode, as
+/// recommended by BIP-173. `ecc` is the error correction level, one of `L`, `M`, `Q` or `H`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_qr_svg(input: &str, ecc: default!(&str, "'M'")) -> String {
+    qr_code(input, ecc)
+        .unwrap_or_else(|e| e.report())
+        .render::<svg::Color>()
+        .quiet_zone(true)
+        .build()
+}
+
+/// Render a checksummed bech32 string as a grayscale PNG QR code, uppercased like
+/// `bech32_qr_svg`.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_qr_png(input: &str, ecc: default!(&str, "'M'")) -> Vec<u8> {
+    let code = qr_code(input, ecc).unwrap_or_else(|e| e.report());
+    let modules = code.width() + 2 * QUIET_ZONE;
+    let size = modules * PNG_MODULE_SIZE;
+    let colors = code.to_colors();
+
+    let mut pixels = Vec::with_capacity(size * size);
+    for y in 0..size {
+        for x in 0..size {
+            let (x, y) = (x / PNG_MODULE_SIZE, y / PNG_MODULE_SIZE);
+            let dark = (QUIET_ZONE..QUIET_ZONE + code.width()).contains(&x)
+                && (QUIET_ZONE..QUIET_ZONE + code.width()).contains(&y)
+                && colors[(y - QUIET_ZONE) * code.width() + (x - QUIET_ZONE)] == Color::Dark;
+            pixels.push(if dark { 0x00 } else { 0xff });
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().expect("error writing PNG header");
+    writer
+        .write_image_data(&pixels)
+        .expect("error writing PNG data");
+    writer.finish().expect("error finishing PNG");
+    png
+}
+
+/// Validates `input` and encodes it in uppercase at the error correction level `ecc`.
+fn qr_code(input: &str, ecc: &str) -> Result<QrCode, Error> {
+    checked(input, None)?;
+
+    let ecc = match ecc.to_ascii_uppercase().as_str() {
+        "L" => EcLevel::L,
+        "M" => EcLevel::M,
+        "Q" => EcLevel::Q,
+        "H" => EcLevel::H,
+        _ => {
+            return Err(Error::Qr {
+                reason: format!("unknown error correction level \"{}\"", ecc),
+            })
+        }
+    };
+
+    QrCode::with_error_correction_level(input.to_ascii_uppercase(), ecc).map_err(|e| Error::Qr {
+        reason: e.to_string(),
+    })
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+    use qrcode::Version;
+
+    const ADDRESS: &str = "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv";
+
+    #[pg_test]
+    fn test_qr_code_uses_alphanumeric_mode() {
+        // 44 characters fit version 3 in alphanumeric mode, but need version 4 as bytes.
+        let code = qr_code(ADDRESS, "M").unwrap();
+        assert_eq!(code.version(), Version::Normal(3));
+    }
+
+    #[pg_test]
+    fn test_bech32_qr_svg() {
+        let svg = bech32_qr_svg(ADDRESS, "M");
+        assert!(svg.contains("<svg"));
+    }
+
+    #[pg_test]
+    fn test_bech32_qr_png() {
+        let png = bech32_qr_png(ADDRESS, "M");
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot render QR code")]
+    fn test_bech32_qr_svg_rejects_unknown_ecc() {
+        bech32_qr_svg(ADDRESS, "X");
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bech32_qr_svg_rejects_bad_checksum() {
+        bech32_qr_svg("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw", "M");
+    }
+}
@@ -0,0 +1,83 @@
+use core::ffi::CStr;
+
+use pgrx::prelude::*;
+use pgrx::{GucContext, GucFlags, GucRegistry, GucSetting};
+
+/// The `Hrp`s (Human Readable Parts) of strings that hold secrets, such as private keys, as a comma
+/// separated list.
+static SECRET_HRPS: GucSetting<Option<&'static CStr>> =
+    GucSetting::<Option<&'static CStr>>::new(Some(c"nsec,age-secret-key-"));
+
+pub fn init() {
+    GucRegistry::define_string_guc(
+        "pg_bech32.secret_hrps",
+        "Human-readable parts of bech32 strings that hold secrets.",
+        "Errors about a string that starts with one of these comma separated human-readable parts \
+         do not include the string, and the statement that raised them is not logged.",
+        &SECRET_HRPS,
+        GucContext::Suset,
+        GucFlags::default(),
+    );
+}
+
+/// Returns the secret `Hrp` that `input` starts with, compared case-insensitively.
+///
+/// Only the prefix is compared, so malformed strings without a separator are covered as well.
+pub fn secret_hrp(input: &str) -> Option<String> {
+    let hrps = SECRET_HRPS.get()?.to_string_lossy();
+    let input = input.trim_start();
+
+    hrps.split(',')
+        .map(str::trim)
+        .filter(|hrp| !hrp.is_empty())
+        .find(|hrp| {
+            input
+                .get(..hrp.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(hrp))
+        })
+        .map(str::to_ascii_lowercase)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    #[pg_test]
+    fn test_secret_hrp() {
+        assert_eq!(
+            secret_hrp("AGE-SECRET-KEY-1QQQQ").as_deref(),
+            Some("age-secret-key-")
+        );
+        assert_eq!(secret_hrp(" nsec1qqqq").as_deref(), Some("nsec"));
+        assert_eq!(secret_hrp("npub1qqqq"), None);
+    }
+
+    #[pg_test]
+    fn test_secret_hrps_setting() {
+        Spi::run("SET pg_bech32.secret_hrps = 'union'").unwrap();
+        assert_eq!(secret_hrp("union1qqqq").as_deref(), Some("union"));
+        assert_eq!(secret_hrp("nsec1qqqq"), None);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string \"nsec1[redacted]\"")]
+    fn test_secret_redacted() {
+        crate::bech32_decode("nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe6");
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string \"nsec1[redacted]\"")]
+    fn test_secret_redacted_in_parallel_mode() {
+        #[cfg(feature = "pg16")]
+        Spi::run("SET debug_parallel_query = on").unwrap();
+        #[cfg(not(feature = "pg16"))]
+        Spi::run("SET force_parallel_mode = on").unwrap();
+        Spi::run(
+            "CREATE TABLE keys (key text);
+             INSERT INTO keys VALUES ('nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe6');",
+        )
+        .unwrap();
+        Spi::run("SELECT bech32_decode(key) FROM keys").unwrap();
+    }
+}
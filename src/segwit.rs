@@ -0,0 +1,122 @@
+use bech32::primitives::decode::SegwitHrpstring;
+use bech32::Fe32;
+use pgrx::prelude::*;
+
+use crate::error::parse_hrp;
+use crate::Error;
+
+extension_sql!(
+    "\
+CREATE TYPE SegwitAddress AS (
+    hrp text,
+    witness_version smallint,
+    program bytea
+);",
+    name = "create_segwit_address_type",
+);
+
+const SEGWIT_COMPOSITE_TYPE: &str = "SegwitAddress";
+
+/// Decode a segwit address (BIP-173/BIP-350) into the `Hrp`, the `witness_version` and the witness
+/// `program`.
+///
+/// Version 0 addresses must use the bech32 checksum and carry a 20 or 32 byte program, later
+/// versions must use the bech32m checksum and carry a 2 to 40 byte program.
+#[pg_extern(immutable, parallel_safe)]
+pub fn segwit_decode(input: &str) -> pgrx::composite_type!('static, SEGWIT_COMPOSITE_TYPE) {
+    let segwit = SegwitHrpstring::new(input).unwrap_or_else(|e| Error::segwit(input, e).report());
+
+    let mut address = PgHeapTuple::new_composite_type(SEGWIT_COMPOSITE_TYPE)
+        .unwrap_or_else(|_| panic!("error creating {} composite type", SEGWIT_COMPOSITE_TYPE));
+    address
+        .set_by_name("hrp", segwit.hrp().to_lowercase())
+        .expect("error setting hrp");
+    address
+        .set_by_name(
+            "witness_version",
+            i16::from(segwit.witness_version().to_u8()),
+        )
+        .expect("error setting witness_version");
+    address
+        .set_by_name("program", segwit.byte_iter().collect::<Vec<u8>>())
+        .expect("error setting program");
+    address
+}
+
+/// Encode the `Hrp` (Human Readable Part), witness version and witness program into a lowercase
+/// segwit address, using bech32 for version 0 and bech32m for later versions.
+#[pg_extern(immutable, parallel_safe)]
+pub fn segwit_encode(hrp: &str, version: i16, program: &[u8]) -> String {
+    encode(hrp, version, program).unwrap_or_else(|e| e.report())
+}
+
+pub(crate) fn encode(hrp: &str, version: i16, program: &[u8]) -> Result<String, Error> {
+    let hrp = parse_hrp(hrp)?;
+    let version = Fe32::try_from(version).map_err(|_| Error::Segwit {
+        reason: format!("invalid segwit witness version: {}", version),
+    })?;
+
+    Ok(bech32::segwit::encode(hrp, version, program)?)
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    // Test vectors from BIP-350.
+    const P2WPKH: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+    const P2TR: &str = "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0";
+
+    #[pg_test]
+    fn test_segwit_decode_v0() {
+        let address = segwit_decode(P2WPKH);
+        assert_eq!(address.get_by_name("hrp").unwrap(), Some("bc"));
+        assert_eq!(
+            address.get_by_name::<i16>("witness_version").unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            address.get_by_name::<Vec<u8>>("program").unwrap(),
+            Some(hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap())
+        );
+    }
+
+    #[pg_test]
+    fn test_segwit_decode_v1() {
+        let address = segwit_decode(P2TR);
+        assert_eq!(
+            address.get_by_name::<i16>("witness_version").unwrap(),
+            Some(1)
+        );
+    }
+
+    #[pg_test]
+    fn test_segwit_encode() {
+        let program = hex::decode("751e76e8199196d454941c45d1b3a323f1433bd6").unwrap();
+        assert_eq!(segwit_encode("bc", 0, &program), P2WPKH);
+    }
+
+    #[pg_test]
+    fn test_segwit_roundtrip_v1() {
+        let result = Spi::get_one::<&str>(&format!(
+            "SELECT segwit_encode(hrp, witness_version, program) FROM segwit_decode('{}')",
+            P2TR
+        ))
+        .unwrap();
+        assert_eq!(result, Some(P2TR));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot encode segwit address")]
+    fn test_segwit_encode_rejects_v0_length() {
+        segwit_encode("bc", 0, &[0; 21]);
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_segwit_decode_rejects_v1_with_bech32_checksum() {
+        // BIP-350: a version 1 program with a bech32 instead of a bech32m checksum.
+        segwit_decode("bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx");
+    }
+}
@@ -0,0 +1,225 @@
+use core::ffi::{c_char, c_int, CStr};
+
+use pgrx::prelude::*;
+use pgrx::Internal;
+
+use crate::types::checked;
+
+/// Token types emitted by `bech32_parser`. `BLANK` matches the default parser, which
+/// `prsd_headline` relies on to tell words from separators. `BECH32_HRP` overlaps the `BECH32`
+/// token before it, so it uses the id of the default parser's hyphenated words, which
+/// `prsd_headline` does not print, to keep the `Hrp` from being printed twice.
+const WORD: c_int = 1;
+const BECH32: c_int = 2;
+const BLANK: c_int = 12;
+const BECH32_HRP: c_int = 16;
+
+const TOKEN_TYPES: &[(c_int, &CStr, &CStr)] = &[
+    (WORD, c"word", c"Word"),
+    (BECH32, c"bech32", c"Bech32 string"),
+    (BLANK, c"blank", c"Space symbols"),
+    (BECH32_HRP, c"bech32hrp", c"Bech32 human-readable part"),
+];
+
+/// The position of the parser in the document.
+struct ParserState {
+    input: *const c_char,
+    len: usize,
+    position: usize,
+    /// The start and length of the `Hrp` of the bech32 string returned last, emitted as the next
+    /// token.
+    pending_hrp: Option<(usize, usize)>,
+}
+
+impl ParserState {
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: Postgres keeps the document alive until the parser is ended.
+        unsafe { core::slice::from_raw_parts(self.input.cast(), self.len) }
+    }
+
+    /// Returns the type, start and length of the next token, or `None` at the end of the document.
+    fn next_token(&mut self) -> Option<(c_int, usize, usize)> {
+        if let Some((start, len)) = self.pending_hrp.take() {
+            return Some((BECH32_HRP, start, len));
+        }
+
+        let start = self.position;
+        let bytes = self.bytes();
+        let is_word = bytes.get(start).copied().map(is_word_byte)?;
+        let len = bytes[start..]
+            .iter()
+            .take_while(|&&b| is_word_byte(b) == is_word)
+            .count();
+        let hrp_len = match is_word {
+            true => core::str::from_utf8(&bytes[start..start + len])
+                .ok()
+                .filter(|token| token.is_ascii())
+                .and_then(|token| checked(token, None).ok())
+                .map(|(parsed, _)| parsed.hrp().len()),
+            false => None,
+        };
+        self.position += len;
+
+        let kind = match (is_word, hrp_len) {
+            (false, _) => BLANK,
+            (true, Some(hrp_len)) => {
+                self.pending_hrp = Some((start, hrp_len));
+                BECH32
+            }
+            (true, None) => WORD,
+        };
+        Some((kind, start, len))
+    }
+}
+
+/// Letters, digits and any byte of a multibyte character form words.
+fn is_word_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || !b.is_ascii()
+}
+
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_prsstart(input: Internal, len: i32) -> Internal {
+    let input = input
+        .unwrap()
+        .expect("bech32_prsstart called without a document")
+        .cast_mut_ptr::<c_char>();
+
+    Internal::new(ParserState {
+        input,
+        len: len as usize,
+        position: 0,
+        pending_hrp: None,
+    })
+}
+
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_prsgettoken(state: Internal, token: Internal, len: Internal) -> i32 {
+    // SAFETY: `state` was created by `bech32_prsstart`, `token` and `len` point to the `char *` and
+    // `int` the token is returned in.
+    unsafe {
+        let state = state
+            .get_mut::<ParserState>()
+            .expect("bech32_prsgettoken called without a parser state");
+        let Some((kind, start, token_len)) = state.next_token() else {
+            return 0;
+        };
+
+        *token
+            .unwrap()
+            .expect("bech32_prsgettoken called without a token")
+            .cast_mut_ptr::<*const c_char>() = state.input.add(start);
+        *len.unwrap()
+            .expect("bech32_prsgettoken called without a token length")
+            .cast_mut_ptr::<c_int>() = token_len as c_int;
+        kind
+    }
+}
+
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_prsend(_state: Internal) {}
+
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_prslextype(_unused: Internal) -> Internal {
+    // SAFETY: the array is terminated by an entry with a `lexid` of 0, as `palloc0` zeroes it.
+    unsafe {
+        let types =
+            pg_sys::palloc0(core::mem::size_of::<pg_sys::LexDescr>() * (TOKEN_TYPES.len() + 1))
+                .cast::<pg_sys::LexDescr>();
+        for (i, (lexid, alias, descr)) in TOKEN_TYPES.iter().enumerate() {
+            *types.add(i) = pg_sys::LexDescr {
+                lexid: *lexid,
+                alias: pg_sys::pstrdup(alias.as_ptr()),
+                descr: pg_sys::pstrdup(descr.as_ptr()),
+            };
+        }
+        Internal::from(Some(pg_sys::Datum::from(types)))
+    }
+}
+
+extension_sql!(
+    "\
+CREATE TEXT SEARCH PARSER bech32_parser (
+    START = bech32_prsstart,
+    GETTOKEN = bech32_prsgettoken,
+    END = bech32_prsend,
+    LEXTYPES = bech32_prslextype,
+    HEADLINE = pg_catalog.prsd_headline
+);
+
+CREATE TEXT SEARCH CONFIGURATION bech32_en (PARSER = bech32_parser);
+ALTER TEXT SEARCH CONFIGURATION bech32_en ADD MAPPING FOR bech32, bech32hrp WITH simple;
+ALTER TEXT SEARCH CONFIGURATION bech32_en ADD MAPPING FOR word WITH english_stem;",
+    name = "create_bech32_text_search",
+    requires = [
+        bech32_prsstart,
+        bech32_prsgettoken,
+        bech32_prsend,
+        bech32_prslextype
+    ],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    fn tokens(input: &str) -> Vec<(c_int, &str)> {
+        let mut state = ParserState {
+            input: input.as_ptr().cast(),
+            len: input.len(),
+            position: 0,
+            pending_hrp: None,
+        };
+        core::iter::from_fn(|| state.next_token())
+            .map(|(kind, start, len)| (kind, &input[start..start + len]))
+            .collect()
+    }
+
+    #[pg_test]
+    fn test_tokens() {
+        assert_eq!(
+            tokens("Sent to COSMOS14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LE3HADA!"),
+            vec![
+                (WORD, "Sent"),
+                (BLANK, " "),
+                (WORD, "to"),
+                (BLANK, " "),
+                (BECH32, "COSMOS14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LE3HADA"),
+                (BECH32_HRP, "COSMOS"),
+                (BLANK, "!"),
+            ]
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_en_matches_any_case() {
+        let result = Spi::get_one::<bool>(
+            "SELECT to_tsvector('bech32_en', 'Refund to COSMOS14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LE3HADA please') \
+             @@ 'cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada'::tsquery",
+        );
+        assert_eq!(result, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_bech32_en_hrp_lexeme() {
+        let result = Spi::get_one::<bool>(
+            "SELECT to_tsvector('bech32_en', 'cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada') \
+             @@ 'cosmos'::tsquery",
+        );
+        assert_eq!(result, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_bech32_en_headline() {
+        let result = Spi::get_one::<String>(
+            "SELECT ts_headline('bech32_en', 'Refund to COSMOS14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LE3HADA please', \
+             'cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada'::tsquery)",
+        );
+        assert_eq!(
+            result,
+            Ok(Some(
+                "Refund to <b>COSMOS14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LE3HADA</b> please".to_string()
+            ))
+        );
+    }
+}
@@ -0,0 +1,366 @@
+use core::cmp::Ordering;
+use core::ffi::CStr;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+
+use crate::Error;
+use bech32::primitives::decode::{CheckedHrpstring, UncheckedHrpstring};
+use bech32::Checksum;
+use pgrx::prelude::*;
+use pgrx::StringInfo;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// The checksum algorithm a bech32 string was encoded with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Variant {
+    Bech32,
+    Bech32m,
+    NoChecksum,
+}
+
+impl Variant {
+    fn to_u8(self) -> u8 {
+        match self {
+            Variant::Bech32 => 0,
+            Variant::Bech32m => 1,
+            Variant::NoChecksum => 2,
+        }
+    }
+
+    /// Parses the `mode` argument accepted by the SQL functions.
+    pub fn from_mode(mode: &str) -> Result<Self, Error> {
+        match mode {
+            "bech32" => Ok(Variant::Bech32),
+            "bech32m" => Ok(Variant::Bech32m),
+            "nochecksum" => Ok(Variant::NoChecksum),
+            _ => Err(Error::UnknownMode(mode.to_string())),
+        }
+    }
+
+    /// The name of the variant, as accepted by [`Variant::from_mode`].
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Variant::Bech32 => "bech32",
+            Variant::Bech32m => "bech32m",
+            Variant::NoChecksum => "nochecksum",
+        }
+    }
+
+    fn from_u8(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Variant::Bech32),
+            1 => Some(Variant::Bech32m),
+            2 => Some(Variant::NoChecksum),
+            _ => None,
+        }
+    }
+}
+
+/// The checksum algorithm as a SQL enum, so that an unknown mode is rejected when the query is
+/// parsed rather than when the function runs.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PostgresEnum)]
+pub enum bech32_variant {
+    bech32,
+    bech32m,
+    nochecksum,
+}
+
+impl From<bech32_variant> for Variant {
+    fn from(variant: bech32_variant) -> Self {
+        match variant {
+            bech32_variant::bech32 => Variant::Bech32,
+            bech32_variant::bech32m => Variant::Bech32m,
+            bech32_variant::nochecksum => Variant::NoChecksum,
+        }
+    }
+}
+
+/// A validated bech32 string.
+///
+/// Values are stored decoded, as the lowercase `Hrp`, the raw payload and the checksum variant, and
+/// are printed back in their canonical lowercase form.
+///
+/// Values compare and hash on the canonical `(hrp, data, variant)` triple, so `COSMOS1…` and
+/// `cosmos1…` are the same address in indexes, joins, `GROUP BY` and `DISTINCT`, while the same
+/// payload under a bech32 and a bech32m checksum, which print differently, are not.
+#[derive(Debug, Clone, PostgresType, PostgresEq, PostgresOrd, PostgresHash)]
+#[inoutfuncs]
+pub struct Bech32 {
+    hrp: String,
+    data: Vec<u8>,
+    variant: Variant,
+}
+
+impl Bech32 {
+    /// Parses a checksummed bech32 or bech32m string.
+    pub fn parse(input: &str) -> Result<Self, Error> {
+        let (checked, variant) = checked(input, None)?;
+        Ok(Bech32::from_checked(checked, variant))
+    }
+
+    /// Parses a string that must use the checksum algorithm of `variant`.
+    pub fn parse_as(input: &str, variant: Variant) -> Result<Self, Error> {
+        let (checked, variant) = checked(input, Some(variant))?;
+        Ok(Bech32::from_checked(checked, variant))
+    }
+
+    fn from_checked(checked: CheckedHrpstring, variant: Variant) -> Self {
+        Bech32 {
+            hrp: checked.hrp().to_lowercase(),
+            data: checked.byte_iter().collect(),
+            variant,
+        }
+    }
+
+    pub fn hrp(&self) -> &str {
+        &self.hrp
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Storage layout: `[variant][hrp length][hrp][data]`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 + self.hrp.len() + self.data.len());
+        bytes.push(self.variant.to_u8());
+        bytes.push(self.hrp.len() as u8);
+        bytes.extend_from_slice(self.hrp.as_bytes());
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (&variant, rest) = bytes.split_first()?;
+        let (&hrp_len, rest) = rest.split_first()?;
+        if rest.len() < hrp_len as usize {
+            return None;
+        }
+        let (hrp, data) = rest.split_at(hrp_len as usize);
+
+        Some(Bech32 {
+            hrp: String::from_utf8(hrp.to_vec()).ok()?,
+            data: data.to_vec(),
+            variant: Variant::from_u8(variant)?,
+        })
+    }
+}
+
+impl PartialEq for Bech32 {
+    fn eq(&self, other: &Self) -> bool {
+        self.hrp == other.hrp && self.data == other.data && self.variant == other.variant
+    }
+}
+
+impl Eq for Bech32 {}
+
+impl PartialOrd for Bech32 {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Bech32 {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&self.hrp, &self.data, self.variant).cmp(&(&other.hrp, &other.data, other.variant))
+    }
+}
+
+impl Hash for Bech32 {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hrp.hash(state);
+        self.data.hash(state);
+        self.variant.hash(state);
+    }
+}
+
+/// The bech32 checksum without the 1023 character limit, for formats such as BOLT11 invoices and
+/// LNURLs that do not limit their length.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum UnboundedBech32 {}
+
+impl Checksum for UnboundedBech32 {
+    type MidstateRepr = u32;
+    const CODE_LENGTH: usize = usize::MAX;
+    const CHECKSUM_LENGTH: usize = bech32::Bech32::CHECKSUM_LENGTH;
+    const GENERATOR_SH: [u32; 5] = bech32::Bech32::GENERATOR_SH;
+    const TARGET_RESIDUE: u32 = bech32::Bech32::TARGET_RESIDUE;
+}
+
+/// Validates the checksum of `input` and strips it.
+///
+/// Without a `variant` either a bech32 or a bech32m checksum is accepted, and the one found is
+/// returned alongside the checked string.
+pub fn checked(
+    input: &str,
+    variant: Option<Variant>,
+) -> Result<(CheckedHrpstring<'_>, Variant), Error> {
+    use bech32::NoChecksum;
+
+    let unchecked = UncheckedHrpstring::new(input).map_err(|e| Error::parse(input, e))?;
+    let detected = match variant {
+        Some(variant) => variant,
+        None if unchecked.has_valid_checksum::<bech32::Bech32>() => Variant::Bech32,
+        None => Variant::Bech32m,
+    };
+    let checked = match detected {
+        Variant::Bech32 => unchecked.validate_and_remove_checksum::<bech32::Bech32>(),
+        Variant::Bech32m => unchecked.validate_and_remove_checksum::<bech32::Bech32m>(),
+        Variant::NoChecksum => unchecked.validate_and_remove_checksum::<NoChecksum>(),
+    }
+    .map_err(|e| Error::checksum(input, e, variant))?;
+
+    Ok((checked, detected))
+}
+
+impl fmt::Display for Bech32 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use bech32::{Hrp, NoChecksum};
+
+        let hrp = Hrp::parse(&self.hrp).map_err(|_| fmt::Error)?;
+        match self.variant {
+            Variant::Bech32 => bech32::encode_lower_to_fmt::<bech32::Bech32, _>(f, hrp, &self.data),
+            Variant::Bech32m => {
+                bech32::encode_lower_to_fmt::<bech32::Bech32m, _>(f, hrp, &self.data)
+            }
+            Variant::NoChecksum => bech32::encode_lower_to_fmt::<NoChecksum, _>(f, hrp, &self.data),
+        }
+        .map_err(|_| fmt::Error)
+    }
+}
+
+impl InOutFuncs for Bech32 {
+    fn input(input: &CStr) -> Self {
+        let input = input
+            .to_str()
+            .unwrap_or_else(|e| Error::utf8(input, e).report());
+        Bech32::parse(input).unwrap_or_else(|e| e.report())
+    }
+
+    fn output(&self, buffer: &mut StringInfo) {
+        buffer.push_str(&self.to_string());
+    }
+}
+
+impl Serialize for Bech32 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.to_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Bech32 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> de::Visitor<'de> for BytesVisitor {
+            type Value = Bech32;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a packed bech32 value")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+                Bech32::from_bytes(v).ok_or_else(|| E::custom("malformed bech32 value"))
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor)
+    }
+}
+
+/// Returns the `Hrp` (Human Readable Part) of a `bech32` value.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_hrp(input: Bech32) -> String {
+    input.hrp
+}
+
+/// Returns the decoded payload of a `bech32` value.
+#[pg_extern(immutable, parallel_safe)]
+pub fn bech32_data(input: Bech32) -> Vec<u8> {
+    input.data
+}
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    #[pg_test]
+    fn test_bech32_type_roundtrip() {
+        let result = Spi::get_one::<String>(
+            "SELECT 'union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv'::bech32::text",
+        )
+        .unwrap();
+        assert_eq!(
+            result.unwrap(),
+            "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv"
+        )
+    }
+
+    #[pg_test]
+    fn test_bech32_type_canonical_case() {
+        let result = Spi::get_one::<String>(
+            "SELECT 'UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV'::bech32::text",
+        )
+        .unwrap();
+        assert_eq!(
+            result.unwrap(),
+            "union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv"
+        )
+    }
+
+    #[pg_test]
+    fn test_bech32_type_accessors() {
+        let bech = Bech32::parse("union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv").unwrap();
+        assert_eq!(bech.variant(), Variant::Bech32);
+        assert_eq!(bech32_hrp(bech.clone()), "union");
+        assert_eq!(
+            hex::encode(bech32_data(bech)),
+            "a833b03d8ed1228c4791cbfab22b3ed57954429f"
+        );
+    }
+
+    #[pg_test]
+    fn test_bech32_type_equality_ignores_case() {
+        let result = Spi::get_one::<bool>(
+            "SELECT 'UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV'::bech32 \
+                  = 'union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv'::bech32",
+        );
+        assert_eq!(result, Ok(Some(true)));
+    }
+
+    #[pg_test]
+    fn test_bech32_type_distinct() {
+        Spi::run(
+            "CREATE TABLE addresses (addr bech32);
+             CREATE INDEX ON addresses (addr);
+             INSERT INTO addresses VALUES
+                ('union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv'),
+                ('UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV'),
+                ('cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada');",
+        )
+        .unwrap();
+        let result = Spi::get_one::<i64>("SELECT count(DISTINCT addr) FROM addresses");
+        assert_eq!(result, Ok(Some(2)));
+    }
+
+    #[pg_test]
+    fn test_bech32_type_equality_includes_variant() {
+        let result = Spi::get_one::<bool>(
+            "SELECT 'union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rv'::bech32 \
+                  = 'union14qemq0vw6y3gc3u3e0aty2e764u4gs5lx6xexw'::bech32",
+        );
+        assert_eq!(result, Ok(Some(false)));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid checksum in bech32 string")]
+    fn test_bech32_type_rejects_bad_checksum() {
+        Spi::run("SELECT 'union14qemq0vw6y3gc3u3e0aty2e764u4gs5lnxk4rw'::bech32").unwrap();
+    }
+}
@@ -0,0 +1,353 @@
+use core::cell::RefCell;
+use core::ffi::CStr;
+use std::collections::HashMap;
+use std::ffi::CString;
+
+use pgrx::prelude::*;
+
+use crate::error::parse_hrp;
+use crate::{Bech32, Error, Variant};
+
+extension_sql!(
+    "\
+CREATE TABLE bech32_typmods (
+    id serial PRIMARY KEY,
+    hrp text NOT NULL,
+    variant text
+);
+CREATE UNIQUE INDEX bech32_typmods_hrp_variant ON bech32_typmods (hrp, coalesce(variant, ''));
+GRANT SELECT ON bech32_typmods TO PUBLIC;",
+    name = "create_bech32_typmods_table",
+);
+
+thread_local! {
+    /// The modifiers read from `bech32_typmods` by this backend. Rows are never updated or deleted,
+    /// so an id always maps to the same modifier.
+    static TYPMODS: RefCell<HashMap<i32, Typmod>> = RefCell::new(HashMap::new());
+}
+
+/// The `Hrp` and checksum variant a `bech32('hrp', 'variant')` column is pinned to.
+///
+/// Postgres stores a type modifier as a non-negative `int4`, which is the id of the modifier's row
+/// in `bech32_typmods`, so any valid `Hrp` of 1 to 83 characters can be used. Rows are added by
+/// `bech32_typmod_in` the first time a modifier is used. They are not dumped, as restoring the
+/// table definitions adds them again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Typmod {
+    hrp: String,
+    variant: Option<Variant>,
+}
+
+impl Typmod {
+    /// Parses the modifiers of `bech32('hrp')` or `bech32('hrp', 'variant')`.
+    pub fn parse(modifiers: &[&str]) -> Result<Self, Error> {
+        let (hrp, variant) = match modifiers {
+            [hrp] => (hrp, None),
+            [hrp, variant] => (hrp, Some(variant)),
+            _ => {
+                return Err(Error::Typmod {
+                    reason: format!("expected 1 or 2 modifiers, got {}", modifiers.len()),
+                })
+            }
+        };
+
+        let hrp = parse_hrp(hrp)
+            .map_err(|_| Error::Typmod {
+                reason: format!(
+                    "the human-readable part \"{}\" is not 1 to 83 printable US-ASCII \
+                     characters in a single case",
+                    hrp
+                ),
+            })?
+            .to_lowercase();
+        let variant = variant
+            .map(|variant| {
+                Variant::from_mode(&variant.to_ascii_lowercase()).map_err(|_| Error::Typmod {
+                    reason: format!("unknown checksum variant \"{}\"", variant),
+                })
+            })
+            .transpose()?;
+
+        Ok(Typmod { hrp, variant })
+    }
+
+    /// Returns the id of this modifier in `bech32_typmods`, adding it if it is not there yet.
+    ///
+    /// A modifier that is not stored yet cannot be added in a read-only transaction, such as on a
+    /// hot standby.
+    pub fn store(&self) -> Result<i32, Error> {
+        let table = table();
+        let args = || {
+            vec![
+                (PgBuiltInOids::TEXTOID.oid(), self.hrp.as_str().into_datum()),
+                (
+                    PgBuiltInOids::TEXTOID.oid(),
+                    self.variant.map(Variant::as_str).into_datum(),
+                ),
+            ]
+        };
+        // `get_one_with_args` fails on an empty result, which means the modifier is not stored.
+        let select = || {
+            Spi::get_one_with_args::<i32>(
+                &format!(
+                    "SELECT id FROM {} WHERE hrp = $1 AND variant IS NOT DISTINCT FROM $2",
+                    table
+                ),
+                args(),
+            )
+            .unwrap_or(None)
+        };
+
+        if let Some(id) = select() {
+            return Ok(id);
+        }
+        // SAFETY: `XactReadOnly` is only written by Postgres between statements.
+        if unsafe { pg_sys::XactReadOnly } {
+            return Err(Error::Typmod {
+                reason: format!(
+                    "the type modifier {} is not stored yet and cannot be added in a read-only \
+                     transaction",
+                    self
+                ),
+            });
+        }
+        // A concurrent transaction may add the same modifier, in which case its row is read back
+        // once it commits.
+        Spi::run_with_args(
+            &format!(
+                "INSERT INTO {} (hrp, variant) VALUES ($1, $2) \
+                 ON CONFLICT (hrp, coalesce(variant, '')) DO NOTHING",
+                table
+            ),
+            Some(args()),
+        )
+        .unwrap_or_else(|e| panic!("error adding bech32 type modifier {}: {}", self, e));
+        Ok(select().unwrap_or_else(|| panic!("bech32 type modifier {} was not added", self)))
+    }
+
+    /// Reads the modifier with the id `typmod` from `bech32_typmods`.
+    pub fn load(typmod: i32) -> Option<Self> {
+        if typmod < 0 {
+            return None;
+        }
+        if let Some(cached) = TYPMODS.with(|typmods| typmods.borrow().get(&typmod).cloned()) {
+            return Some(cached);
+        }
+
+        let query = format!("SELECT hrp, variant FROM {} WHERE id = $1", table());
+        let args = vec![(PgBuiltInOids::INT4OID.oid(), typmod.into_datum())];
+        // Reading an empty result fails, which means there is no such modifier.
+        let (hrp, variant) = Spi::connect(|client| {
+            client
+                .select(&query, Some(1), Some(args))?
+                .first()
+                .get_two::<String, String>()
+        })
+        .unwrap_or((None, None));
+        let loaded = Typmod {
+            hrp: hrp?,
+            variant: variant.map(|variant| {
+                Variant::from_mode(&variant).expect("bech32_typmods holds a known variant")
+            }),
+        };
+
+        TYPMODS.with(|typmods| {
+            typmods.borrow_mut().insert(typmod, loaded.clone());
+        });
+        Some(loaded)
+    }
+
+    /// Checks that `value` has the `Hrp` and checksum variant of this modifier.
+    pub fn check(&self, value: &Bech32) -> Result<(), Error> {
+        let reason = if value.hrp() != self.hrp {
+            format!("The human-readable part is \"{}\"", value.hrp())
+        } else if self
+            .variant
+            .is_some_and(|variant| variant != value.variant())
+        {
+            format!("The checksum variant is {}", value.variant().as_str())
+        } else {
+            return Ok(());
+        };
+
+        Err(Error::TypmodMismatch {
+            value: value.to_string(),
+            typmod: self.to_string(),
+            reason,
+        })
+    }
+}
+
+impl core::fmt::Display for Typmod {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        // The `Hrp` may contain a quote, which is doubled so the type can be parsed back.
+        let hrp = self.hrp.replace('\'', "''");
+        match self.variant {
+            Some(variant) => write!(f, "('{}','{}')", hrp, variant.as_str()),
+            None => write!(f, "('{}')", hrp),
+        }
+    }
+}
+
+/// Returns the schema qualified name of `bech32_typmods`, as the functions that read it may run
+/// with any `search_path`, such as the empty one `pg_dump` sets.
+fn table() -> String {
+    Spi::connect(|client| {
+        client
+            .select(
+                "SELECT pg_catalog.format('%I.bech32_typmods', n.nspname) \
+                 FROM pg_catalog.pg_extension e \
+                 JOIN pg_catalog.pg_namespace n ON n.oid = e.extnamespace \
+                 WHERE e.extname = 'pg_bech32'",
+                Some(1),
+                None,
+            )?
+            .first()
+            .get_one::<String>()
+    })
+    .ok()
+    .flatten()
+    .expect("pg_bech32 extension is installed")
+}
+
+/// Adds the modifier to `bech32_typmods` when it is first used, with the rights of the extension
+/// owner, so any role that can create a table can use a new modifier. The `search_path` is pinned,
+/// so the caller's schemas cannot change the operators its queries resolve to.
+#[pg_extern(security_definer)]
+#[search_path(pg_catalog, pg_temp)]
+pub fn bech32_typmod_in(modifiers: Array<&CStr>) -> i32 {
+    let modifiers: Vec<&str> = modifiers
+        .iter()
+        .map(|modifier| {
+            modifier
+                .and_then(|modifier| modifier.to_str().ok())
+                .unwrap_or_else(|| {
+                    Error::Typmod {
+                        reason: "modifiers must be non-null UTF8 strings".into(),
+                    }
+                    .report()
+                })
+        })
+        .collect();
+
+    Typmod::parse(&modifiers)
+        .and_then(|typmod| typmod.store())
+        .unwrap_or_else(|e| e.report())
+}
+
+#[pg_extern(stable, parallel_safe)]
+pub fn bech32_typmod_out(typmod: i32) -> CString {
+    let typmod = Typmod::load(typmod).map(|typmod| typmod.to_string());
+    CString::new(typmod.unwrap_or_default()).expect("type modifier contains a nul byte")
+}
+
+/// Casts a `bech32` value to a column type with a modifier, rejecting values with a different `Hrp`
+/// or checksum variant.
+#[pg_extern(stable, parallel_safe)]
+pub fn bech32_enforce_typmod(input: Bech32, typmod: i32, _explicit: bool) -> Bech32 {
+    if let Some(typmod) = Typmod::load(typmod) {
+        typmod.check(&input).unwrap_or_else(|e| e.report());
+    }
+    input
+}
+
+extension_sql!(
+    "\
+ALTER TYPE Bech32 SET (TYPMOD_IN = bech32_typmod_in, TYPMOD_OUT = bech32_typmod_out);
+CREATE CAST (Bech32 AS Bech32)
+    WITH FUNCTION bech32_enforce_typmod(Bech32, integer, boolean) AS IMPLICIT;",
+    name = "create_bech32_typmod",
+    requires = [bech32_typmod_in, bech32_typmod_out, bech32_enforce_typmod],
+);
+
+#[cfg(any(test, feature = "pg_test"))]
+#[pg_schema]
+mod tests {
+    use super::*;
+
+    #[pg_test]
+    fn test_typmod_roundtrip() {
+        for modifiers in [
+            &["union"][..],
+            &["bc", "bech32m"][..],
+            &["cosmosvaloper", "nochecksum"][..],
+            &["age-secret-key-"][..],
+        ] {
+            let typmod = Typmod::parse(modifiers).unwrap();
+            let id = typmod.store().unwrap();
+            assert!(id >= 0);
+            assert_eq!(typmod.store(), Ok(id));
+            assert_eq!(Typmod::load(id), Some(typmod));
+        }
+        assert_ne!(
+            Typmod::parse(&["union"]).unwrap().store().unwrap(),
+            Typmod::parse(&["union", "bech32"])
+                .unwrap()
+                .store()
+                .unwrap()
+        );
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "cannot be added in a read-only transaction")]
+    fn test_typmod_new_in_read_only_transaction() {
+        Spi::run("SET transaction_read_only = on").unwrap();
+        Spi::run("SELECT NULL::bech32('unstored')").unwrap();
+    }
+
+    #[pg_test]
+    fn test_typmod_format_type() {
+        Spi::run("CREATE TABLE outputs (addr bech32('BC', 'bech32m'))").unwrap();
+        let result = Spi::get_one::<String>(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+             WHERE attrelid = 'outputs'::regclass AND attname = 'addr'",
+        );
+        assert_eq!(result, Ok(Some("bech32('bc','bech32m')".to_string())));
+    }
+
+    #[pg_test]
+    fn test_typmod_accepts_matching_value() {
+        Spi::run(
+            "CREATE TABLE accounts (owner bech32('union'));
+             INSERT INTO accounts VALUES ('UNION14QEMQ0VW6Y3GC3U3E0ATY2E764U4GS5LNXK4RV');",
+        )
+        .unwrap();
+    }
+
+    #[pg_test]
+    fn test_typmod_long_hrp() {
+        Spi::run(
+            "CREATE TABLE validators (operator bech32('cosmosvaloper'));
+             INSERT INTO validators VALUES ('cosmosvaloper14qemq0vw6y3gc3u3e0aty2e764u4gs5lu9rgpw');",
+        )
+        .unwrap();
+        let result = Spi::get_one::<String>(
+            "SELECT format_type(atttypid, atttypmod) FROM pg_attribute \
+             WHERE attrelid = 'validators'::regclass AND attname = 'operator'",
+        );
+        assert_eq!(result, Ok(Some("bech32('cosmosvaloper')".to_string())));
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "does not match type bech32('union')")]
+    fn test_typmod_rejects_other_hrp() {
+        Spi::run(
+            "CREATE TABLE accounts (owner bech32('union'));
+             INSERT INTO accounts VALUES ('cosmos14qemq0vw6y3gc3u3e0aty2e764u4gs5le3hada');",
+        )
+        .unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "does not match type bech32('bc','bech32m')")]
+    fn test_typmod_rejects_other_variant() {
+        Spi::run("SELECT 'bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4'::bech32('bc', 'bech32m')")
+            .unwrap();
+    }
+
+    #[pg_test]
+    #[should_panic(expected = "invalid type modifier for type bech32")]
+    fn test_typmod_rejects_invalid_hrp() {
+        Spi::run("CREATE TABLE accounts (owner bech32('Celestia'))").unwrap();
+    }
+}